@@ -177,11 +177,8 @@ pub fn compile_and_get_stats(
 
     // We want to generate as unique symbols as possible
     let mut rustflags = "-Csymbol-mangling-version=v0".to_string();
-    match backend {
-        CodegenBackend::Llvm => {}
-        CodegenBackend::Cranelift => {
-            rustflags.push_str(" -Zcodegen-backend=cranelift");
-        }
+    if let Some(flag) = backend.rustc_flag() {
+        rustflags.push_str(&format!(" {flag}"));
     }
 
     cmd.env("RUSTC", &toolchain.components.rustc);
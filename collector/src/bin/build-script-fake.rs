@@ -0,0 +1,36 @@
+//! A shim that stands in for a compiled build script during benchmarking, so
+//! that the wall time of running `build.rs` can be measured separately from
+//! compiling it. `rustc-fake` installs it in place of the binary emitted for
+//! `build_script_build` crates and the collector sums the recorded times
+//! afterwards (see `!build-script-run:`).
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process::Command;
+use std::time::Instant;
+
+fn main() {
+    let mut args = env::args_os().skip(1);
+    let real = args.next().expect("path to the real build script");
+
+    // Stdio is inherited, so the `cargo:` directives printed by the build
+    // script still reach cargo unchanged.
+    let start = Instant::now();
+    let status = Command::new(&real)
+        .args(args)
+        .status()
+        .expect("failed to spawn build script");
+    let elapsed = start.elapsed();
+
+    // Cargo can re-run a build script several times per benchmark (its
+    // fingerprint can change between scenarios), so append rather than
+    // overwrite; the collector sums the entries.
+    if let Some(file) = env::var_os("BUILD_SCRIPT_TIMES_FILE") {
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(file) {
+            let _ = writeln!(file, "run:{}", elapsed.as_secs_f64());
+        }
+    }
+
+    std::process::exit(status.code().unwrap_or(1));
+}
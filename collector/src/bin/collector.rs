@@ -32,13 +32,21 @@ use collector::artifact_stats::{
 use collector::codegen::{codegen_diff, CodegenType};
 use collector::compile::benchmark::category::Category;
 use collector::compile::benchmark::codegen_backend::CodegenBackend;
+use collector::compile::benchmark::incompatible;
 use collector::compile::benchmark::profile::Profile;
+use collector::compile::benchmark::sanitizer::Sanitizer;
 use collector::compile::benchmark::scenario::Scenario;
 use collector::compile::benchmark::{
-    compile_benchmark_dir, get_compile_benchmarks, ArtifactType, Benchmark, BenchmarkName,
+    compile_benchmark_dir, get_compile_benchmarks, jobserver_token_count, shuffle_benchmarks,
+    AdaptiveRunCount, ArtifactType, Benchmark, BenchmarkName, GroupPreparationCache,
+};
+use collector::compile::execute::bencher::{BenchProcessor, InMemoryProcessor, StatAggregation};
+use collector::compile::execute::{
+    check_keep_going_supported, compiler_invocation_budget_exhausted, compiler_invocation_count,
+    cross_target, set_compiler_invocation_limit,
 };
-use collector::compile::execute::bencher::BenchProcessor;
 use collector::compile::execute::profiler::{ProfileProcessor, Profiler};
+use collector::compile::execute::stat_transform::StatTransform;
 use collector::runtime::{
     bench_runtime, get_runtime_benchmark_groups, prepare_runtime_benchmark_suite,
     runtime_benchmark_dir, BenchmarkFilter, BenchmarkSuite, BenchmarkSuiteCompilation,
@@ -51,12 +59,148 @@ use collector::toolchain::{
 };
 use collector::utils::cachegrind::cachegrind_diff;
 use collector::utils::{is_installed, wait_for_future};
+#[cfg(target_os = "linux")]
+use collector::utils::cgroup;
 use collector::{utils, CollectorCtx, CollectorStepBuilder};
 use database::{ArtifactId, ArtifactIdNumber, Commit, CommitType, Connection, Pool};
 
-fn n_normal_benchmarks_remaining(n: usize) -> String {
-    let suffix = if n == 1 { "" } else { "s" };
-    format!("{} normal benchmark{} remaining", n, suffix)
+/// Tracks the overall progress of the serial benchmark loop and produces
+/// `benchmark N of M` intro lines with a rolling ETA based on the average
+/// time per finished benchmark. Every benchmark announcing its position and
+/// the expected remaining time makes stalls obvious in CI logs.
+struct ProgressReporter {
+    total: usize,
+    finished: usize,
+    started: std::time::Instant,
+}
+
+impl ProgressReporter {
+    fn new(total: usize) -> Self {
+        ProgressReporter {
+            total,
+            finished: 0,
+            started: std::time::Instant::now(),
+        }
+    }
+
+    /// Returns the intro line for the benchmark at 1-based `position`;
+    /// `finish` must be called once that benchmark is done or skipped.
+    fn intro(&self, position: usize, benchmark: &BenchmarkName) -> String {
+        let mut line = format!("benchmark {} of {}: {}", position, self.total, benchmark);
+        if self.finished > 0 {
+            let eta = self.started.elapsed() / self.finished as u32
+                * (self.total - self.finished) as u32;
+            let secs = eta.as_secs();
+            line.push_str(&format!(
+                " (ETA {}:{:02}:{:02})",
+                secs / 3600,
+                (secs % 3600) / 60,
+                secs % 60
+            ));
+        }
+        line
+    }
+
+    fn finish(&mut self) {
+        self.finished += 1;
+    }
+}
+
+/// Outcome of one benchmark, as reported in the JUnit XML file.
+enum JunitOutcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+/// Writes a JUnit-format XML report with one test case per benchmark, so that
+/// CI systems can surface failed and skipped benchmarks in their standard
+/// test report UI. Opt-in: only created when the `RUSTC_PERF_JUNIT`
+/// environment variable names the output file. Covers the serially executed
+/// compile benchmarks.
+struct JunitReporter {
+    path: PathBuf,
+    cases: Vec<(String, JunitOutcome, std::time::Duration)>,
+}
+
+impl JunitReporter {
+    fn from_env() -> Option<Self> {
+        std::env::var_os("RUSTC_PERF_JUNIT").map(|path| JunitReporter {
+            path: PathBuf::from(path),
+            cases: Vec::new(),
+        })
+    }
+
+    fn record(
+        &mut self,
+        benchmark: &BenchmarkName,
+        outcome: JunitOutcome,
+        duration: std::time::Duration,
+    ) {
+        self.cases.push((benchmark.0.clone(), outcome, duration));
+    }
+
+    fn write(&self) -> anyhow::Result<()> {
+        fn escape(text: &str) -> String {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        }
+
+        let failures = self
+            .cases
+            .iter()
+            .filter(|(_, outcome, _)| matches!(outcome, JunitOutcome::Failed(_)))
+            .count();
+        let skipped = self
+            .cases
+            .iter()
+            .filter(|(_, outcome, _)| matches!(outcome, JunitOutcome::Skipped(_)))
+            .count();
+        let total: f64 = self
+            .cases
+            .iter()
+            .map(|(_, _, duration)| duration.as_secs_f64())
+            .sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"rustc-perf\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            self.cases.len(),
+            failures,
+            skipped,
+            total
+        ));
+        for (name, outcome, duration) in &self.cases {
+            let open = format!(
+                "  <testcase name=\"{}\" time=\"{:.3}\"",
+                escape(name),
+                duration.as_secs_f64()
+            );
+            match outcome {
+                JunitOutcome::Passed => {
+                    xml.push_str(&format!("{open}/>\n"));
+                }
+                JunitOutcome::Failed(message) => {
+                    xml.push_str(&format!(
+                        "{open}>\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                        escape(message)
+                    ));
+                }
+                JunitOutcome::Skipped(message) => {
+                    xml.push_str(&format!(
+                        "{open}>\n    <skipped message=\"{}\"/>\n  </testcase>\n",
+                        escape(message)
+                    ));
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+
+        std::fs::write(&self.path, xml)
+            .with_context(|| format!("cannot write JUnit report to {:?}", self.path))
+    }
 }
 
 struct BenchmarkErrors(usize);
@@ -94,8 +238,53 @@ struct CompileBenchmarkConfig {
     scenarios: Vec<Scenario>,
     backends: Vec<CodegenBackend>,
     iterations: Option<usize>,
+    /// When set, `iterations` acts as a minimum and each benchmark keeps
+    /// iterating until its measurements are stable (or the bound is reached).
+    adaptive: Option<AdaptiveRunCount>,
     is_self_profile: bool,
     bench_rustc: bool,
+    /// When set, the benchmark execution order is shuffled based on this seed.
+    shuffle_seed: Option<u64>,
+    /// When set, the collection stops gracefully once this wall-clock budget
+    /// has been exceeded.
+    max_duration: Option<Duration>,
+    /// Declarative transform applied to the measured statistics before they
+    /// are recorded. Empty by default.
+    stat_transform: StatTransform,
+    /// When set, results are additionally exported in a Criterion-compatible
+    /// JSON layout rooted at this directory.
+    criterion_export: Option<PathBuf>,
+    /// When set, every completed measurement is additionally written to
+    /// stdout as a single JSON line, for piping into other tools.
+    json_stdout: bool,
+    /// When set, the duration of `cargo metadata` is measured once per
+    /// benchmark and recorded as collection metadata.
+    measure_resolve_time: bool,
+    /// When set, a single aggregated value per statistic is recorded instead
+    /// of every iteration's value.
+    stat_aggregation: Option<StatAggregation>,
+    /// When set, the collection stops gracefully once this many measured rustc
+    /// invocations have been performed.
+    compiler_invocation_limit: Option<usize>,
+    /// When set, the fixed startup overhead of the benchmarked rustc is
+    /// measured once (by compiling an empty crate) and recorded as collection
+    /// metadata.
+    measure_rustc_startup: bool,
+    /// When set, independent benchmarks are measured concurrently by a
+    /// bounded worker pool instead of sequentially.
+    parallel: Option<ParallelBenchConfig>,
+}
+
+/// Configuration of the opt-in parallel measurement mode. Concurrent
+/// compilations contend for CPU, caches and memory bandwidth, so wall-time
+/// and cycle measurements from this mode are not comparable with sequential
+/// runs; it is intended for throughput-oriented collections (e.g. smoke
+/// tests, or instruction counts of `Check` builds on large machines).
+struct ParallelBenchConfig {
+    /// Number of worker threads, each measuring one benchmark at a time.
+    jobs: usize,
+    /// Path of the database, from which each worker opens its own connection.
+    db: String,
 }
 
 struct RuntimeBenchmarkConfig {
@@ -117,6 +306,41 @@ impl RuntimeBenchmarkConfig {
 struct SharedBenchmarkConfig {
     artifact_id: ArtifactId,
     toolchain: Toolchain,
+    /// Free-form user-supplied `key=value` tags, recorded as collection
+    /// metadata under `tag:<key>` entries.
+    tags: Vec<(String, String)>,
+}
+
+/// The `-Cdebuginfo` levels that rustc understands, used to validate
+/// `--debuginfo-levels` before any benchmark runs.
+const KNOWN_DEBUGINFO_LEVELS: &[&str] = &[
+    "0",
+    "1",
+    "2",
+    "none",
+    "limited",
+    "full",
+    "line-tables-only",
+    "line-directives-only",
+];
+
+/// Validates a user-supplied `-Cdebuginfo` level.
+fn parse_debuginfo_level(value: &str) -> Result<String, String> {
+    if KNOWN_DEBUGINFO_LEVELS.contains(&value) {
+        Ok(value.to_string())
+    } else {
+        Err(format!(
+            "unknown debuginfo level `{value}`: expected one of {KNOWN_DEBUGINFO_LEVELS:?}"
+        ))
+    }
+}
+
+/// Parses a user-supplied `key=value` experiment tag.
+fn parse_tag(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected `key=value`, found `{value}`")),
+    }
 }
 
 fn check_measureme_installed() -> Result<(), String> {
@@ -157,7 +381,7 @@ fn generate_cachegrind_diffs(
                     Scenario::Full | Scenario::IncrFull | Scenario::IncrUnchanged => {
                         vec![format!("{:?}", scenario)]
                     }
-                    Scenario::IncrPatched => (0..benchmark.patches.len())
+                    Scenario::IncrPatched | Scenario::IncrReverted => (0..benchmark.patches.len())
                         .map(|i| format!("{:?}{}", scenario, i))
                         .collect::<Vec<_>>(),
                 }
@@ -195,12 +419,22 @@ fn profile_compile(
     profiles: &[Profile],
     scenarios: &[Scenario],
     backends: &[CodegenBackend],
+    runs: usize,
+    profile_only_first_iteration: bool,
+    compress_output: bool,
+    archive_output: bool,
     errors: &mut BenchmarkErrors,
 ) {
     eprintln!("Profiling {} with {:?}", toolchain.id, profiler);
     if let Profiler::SelfProfile = profiler {
         check_measureme_installed().unwrap();
     }
+    // Fail before preparing any benchmark if the profiler's tool is missing.
+    if let Err(error) = profiler.check_available() {
+        eprintln!("collector error: {error:#}");
+        errors.incr();
+        return;
+    }
 
     let error_count: usize = benchmarks
         .par_iter()
@@ -208,14 +442,22 @@ fn profile_compile(
         .map(|(i, benchmark)| {
             let benchmark_id = format!("{} ({}/{})", benchmark.name, i + 1, benchmarks.len());
             eprintln!("Executing benchmark {benchmark_id}");
-            let mut processor = ProfileProcessor::new(profiler, out_dir, &toolchain.id);
+            let mut processor = ProfileProcessor::new(
+                profiler,
+                out_dir,
+                &toolchain.id,
+                profile_only_first_iteration,
+            );
             let result = wait_for_future(benchmark.measure(
                 &mut processor,
                 profiles,
                 scenarios,
                 backends,
                 toolchain,
-                Some(1),
+                Some(runs.max(1)),
+                None,
+                None,
+                None,
             ));
             eprintln!("Finished benchmark {benchmark_id}");
 
@@ -224,15 +466,139 @@ fn profile_compile(
                     "collector error: Failed to profile '{}' with {:?}, recorded: {:?}",
                     benchmark.name, profiler, s
                 );
-                1
-            } else {
-                0
+                return 1;
             }
+
+            if compress_output || archive_output {
+                if let Err(error) = compress_profile_artifacts(
+                    out_dir,
+                    &toolchain.id,
+                    &benchmark.name.to_string(),
+                    profiler,
+                    archive_output,
+                ) {
+                    eprintln!(
+                        "collector error: Failed to compress profiler output of '{}': {:#}",
+                        benchmark.name, error
+                    );
+                    return 1;
+                }
+            }
+            0
         })
         .sum();
     errors.add(error_count);
 }
 
+/// Compresses the profiler artifacts produced for a single benchmark, using
+/// the same Snappy framing as the self-profile S3 upload. When `archive` is
+/// true, the artifacts are instead bundled into one `.tar.sz` archive per
+/// benchmark, together with a `manifest.json` describing each entry
+/// (benchmark, profile, scenario and profiler), and the loose files are
+/// removed.
+fn compress_profile_artifacts(
+    out_dir: &Path,
+    id: &str,
+    benchmark: &str,
+    profiler: Profiler,
+    archive: bool,
+) -> anyhow::Result<()> {
+    // Artifacts are named `$PREFIX-$ID-$BENCHMARK-$PROFILE-$SCENARIO`, so this
+    // infix identifies the outputs belonging to this benchmark even when
+    // several benchmarks share the output directory.
+    let infix = format!("-{id}-{benchmark}-");
+    let mut artifacts = Vec::new();
+    for entry in fs::read_dir(out_dir).context("read output directory")? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.contains(&infix) || name.ends_with(".sz") {
+            continue;
+        }
+        artifacts.push((name, entry.path()));
+    }
+    artifacts.sort();
+
+    if archive {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut manifest = Vec::new();
+        for (name, path) in &artifacts {
+            if path.is_dir() {
+                builder.append_dir_all(name, path)?;
+            } else {
+                builder.append_path_with_name(path, name)?;
+            }
+            // Profile names contain no dashes, so the first dash after the
+            // infix separates the profile from the scenario.
+            let rest = &name[name.find(&infix).unwrap() + infix.len()..];
+            let (profile, scenario) = rest.split_once('-').unwrap_or((rest, ""));
+            manifest.push(serde_json::json!({
+                "file": name,
+                "benchmark": benchmark,
+                "profile": profile,
+                "scenario": scenario,
+                "profiler": format!("{profiler:?}"),
+            }));
+        }
+        let manifest = serde_json::to_vec_pretty(&serde_json::json!({
+            "artifact_id": id,
+            "benchmark": benchmark,
+            "profiler": format!("{profiler:?}"),
+            "entries": manifest,
+        }))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "manifest.json", &manifest[..])?;
+
+        let archive_path = out_dir.join(format!("{id}-{benchmark}.tar.sz"));
+        fs::write(&archive_path, snappy_compress(&builder.into_inner()?)?)
+            .with_context(|| format!("write archive {}", archive_path.display()))?;
+        for (_, path) in &artifacts {
+            remove_path(path)?;
+        }
+    } else {
+        for (name, path) in &artifacts {
+            let compressed = if path.is_dir() {
+                // Directories (e.g. self-profile data) are bundled into a tar
+                // stream first, so that they compress into a single file.
+                let mut builder = tar::Builder::new(Vec::new());
+                builder.append_dir_all(name, path)?;
+                (
+                    out_dir.join(format!("{name}.tar.sz")),
+                    snappy_compress(&builder.into_inner()?)?,
+                )
+            } else {
+                (
+                    out_dir.join(format!("{name}.sz")),
+                    snappy_compress(&fs::read(path)?)?,
+                )
+            };
+            fs::write(&compressed.0, compressed.1)
+                .with_context(|| format!("write {}", compressed.0.display()))?;
+            remove_path(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn snappy_compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut encoder = snap::read::FrameEncoder::new(data);
+    let mut compressed = Vec::new();
+    encoder.read_to_end(&mut compressed)?;
+    Ok(compressed)
+}
+
+fn remove_path(path: &Path) -> anyhow::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path).with_context(|| format!("remove {}", path.display()))?;
+    } else {
+        fs::remove_file(path).with_context(|| format!("remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
 fn main() {
     match main_result() {
         Ok(code) => process::exit(code),
@@ -551,6 +917,126 @@ enum Commands {
         #[arg(long, default_value = "1")]
         iterations: usize,
 
+        /// Instead of running a fixed number of iterations, keep iterating
+        /// until the coefficient of variation of `instructions:u` (falling
+        /// back to `wall-time`) across the finished iterations drops below
+        /// this threshold (e.g. `0.005`). `--iterations` then acts as the
+        /// minimum number of runs. Requires the `Full` scenario.
+        #[arg(long)]
+        adaptive_cv_threshold: Option<f64>,
+
+        /// Upper bound on the number of iterations per benchmark when
+        /// `--adaptive-cv-threshold` is used.
+        #[arg(long, default_value = "10")]
+        adaptive_max_runs: usize,
+
+        /// Measure this many independent benchmarks concurrently. Concurrent
+        /// compilations contend for CPU and caches, so the resulting
+        /// wall-time and cycle measurements are NOT comparable with
+        /// sequential runs; use this only for throughput-oriented
+        /// collections (e.g. instruction counts of `Check` builds on a
+        /// large machine, or smoke tests).
+        #[arg(long, default_value = "1")]
+        parallel_jobs: usize,
+
+        /// Shuffle the benchmark execution order based on the given seed, so
+        /// that systematic machine-state effects (thermal, caches) do not
+        /// always affect the same benchmarks. The default is a deterministic
+        /// (alphabetical) order.
+        #[arg(long)]
+        shuffle_seed: Option<u64>,
+
+        /// Stop the run gracefully after this many minutes, instead of being
+        /// hard-killed by an external time limit. The budget is checked
+        /// between benchmarks, so it can be overshot by the duration of a
+        /// single benchmark. Since interrupted collections are resumed,
+        /// a large run can be chunked by invoking the collector repeatedly
+        /// with this flag.
+        #[arg(long)]
+        max_duration: Option<u64>,
+
+        /// Path to a JSON file with declarative rules (rename, scale, derive)
+        /// applied to the measured statistics before they are recorded.
+        #[arg(long)]
+        stat_transform: Option<PathBuf>,
+
+        /// Additionally export the results into the given directory in a
+        /// Criterion-compatible JSON layout, for reuse of Criterion-based
+        /// analysis tooling.
+        #[arg(long)]
+        criterion_export: Option<PathBuf>,
+
+        /// Write every completed measurement to stdout as a single JSON line
+        /// (`{"benchmark", "profile", "scenario", "stats": {...}}`), flushed
+        /// immediately, so the collector can be piped into `jq` or similar
+        /// tools in real time. Human-readable logging stays on stderr, so
+        /// stdout is pure data.
+        #[arg(long)]
+        json_stdout: bool,
+
+        /// Measure how long `cargo metadata` (dependency resolution) takes
+        /// for each benchmark and record it as collection metadata, separate
+        /// from the compile-time statistics.
+        #[arg(long)]
+        measure_resolve_time: bool,
+
+        /// Record a single aggregated value per statistic instead of every
+        /// iteration's value. `min` is usually the most reproducible choice
+        /// for hardware counters such as `instructions:u`, while `median`
+        /// suits noisy, wall-time-like metrics. By default each iteration is
+        /// recorded separately and aggregation happens at query time.
+        #[arg(long, value_enum)]
+        stat_aggregation: Option<StatAggregation>,
+
+        /// Stop the run gracefully after this many measured rustc
+        /// invocations, regardless of how many benchmarks or scenarios
+        /// remain. This caps the total work done and is mainly useful for
+        /// fast smoke tests (e.g. in CI) that only need to check that
+        /// collection works at all.
+        #[arg(long)]
+        compiler_invocation_limit: Option<usize>,
+
+        /// Verify before the run starts that `perf stat` can actually collect
+        /// every counter the run intends to gather, by measuring a trivial
+        /// command. Fails with remediation guidance (e.g. adjusting
+        /// `kernel.perf_event_paranoid`) instead of wasting hours of
+        /// collection on a machine where counters are unavailable.
+        #[arg(long)]
+        check_perf_counters: bool,
+
+        /// Measure the fixed startup overhead of the benchmarked rustc once,
+        /// by compiling an empty crate, and record it as collection metadata
+        /// (`rustc-startup-instructions` / `rustc-startup-wall-time`). This
+        /// helps to separate "the compiler got slower to start" from "this
+        /// benchmark got more expensive".
+        #[arg(long)]
+        measure_rustc_startup: bool,
+
+        /// Attach a free-form `key=value` tag to the collection, stored as
+        /// collection metadata under a `tag:<key>` entry. May be repeated.
+        /// Useful for distinguishing experiments later (e.g.
+        /// `--tag experiment=lto-study --tag ticket=1234`).
+        #[arg(long = "tag", value_parser = parse_tag)]
+        tags: Vec<(String, String)>,
+
+        /// Build the leaf crate of each benchmark with the given
+        /// sanitizer(s) enabled (`-Zsanitizer=`), measuring each benchmark
+        /// under a sanitizer-tagged name (e.g. `syn-1.0.89-asan`). A
+        /// benchmark that cannot build with one sanitizer fails only that
+        /// variant. Sanitized artifacts are noticeably larger, which shows up
+        /// in the recorded `size:` statistics.
+        #[arg(long = "sanitizers", value_parser = EnumArgParser::<Sanitizer>::default())]
+        sanitizers: Option<MultiEnumValue<Sanitizer>>,
+
+        /// Build the leaf crate of each benchmark with the given
+        /// `-Cdebuginfo` level(s) (comma-separated, e.g. `0,2,line-tables-only`),
+        /// measuring each benchmark once per level under a debuginfo-tagged
+        /// name (e.g. `syn-1.0.89-debuginfo-2`). This makes the compile-time
+        /// cost curve of debuginfo visible across the suite; combine with
+        /// `RUSTC_PERF_SECTION_SIZES` to also see the artifact-size side.
+        #[arg(long, value_delimiter = ',', value_parser = parse_debuginfo_level)]
+        debuginfo_levels: Vec<String>,
+
         #[command(flatten)]
         self_profile: SelfProfileOption,
 
@@ -558,6 +1044,29 @@ enum Commands {
         purge: PurgeOption,
     },
 
+    /// Measures the noise floor of this machine by running a single small
+    /// benchmark many times and reporting the observed variation of the
+    /// results. This helps to decide whether e.g. a 1% change in a real run
+    /// can be distinguished from noise on this machine.
+    Calibrate {
+        /// The path to the local rustc to use
+        // Not a `PathBuf` because it can be a file path *or* a `+`-prefixed
+        // toolchain name, and `PathBuf` doesn't work well for the latter.
+        rustc: String,
+
+        /// The path to the local Cargo to use
+        #[arg(long)]
+        cargo: Option<PathBuf>,
+
+        /// The benchmark that is measured repeatedly
+        #[arg(long, default_value = "helloworld")]
+        benchmark: String,
+
+        /// How many times the benchmark is measured
+        #[arg(long, default_value = "20")]
+        iterations: usize,
+    },
+
     /// Benchmarks the next commit or release for perf.rust-lang.org
     BenchNext {
         /// Site URL
@@ -608,6 +1117,26 @@ enum Commands {
         /// This flag is only supported for certain profilers
         #[arg(long, short = 'j', default_value = "1")]
         jobs: u64,
+
+        /// How many iterations of each benchmark should be executed.
+        #[arg(long, default_value = "1")]
+        runs: usize,
+
+        /// Only attach the profiler to the first iteration; run the remaining
+        /// iterations as plain builds. Useful to cut the overhead of expensive
+        /// profilers (e.g. Callgrind, DHAT) when `--runs` is larger than one.
+        #[arg(long)]
+        profile_only_first_iteration: bool,
+
+        /// Compress each profiler artifact (using Snappy framing, like the
+        /// self-profile S3 upload) after a benchmark finishes.
+        #[arg(long)]
+        compress_output: bool,
+
+        /// Bundle all profiler artifacts of each benchmark into a single
+        /// compressed archive with a `manifest.json` describing the contents.
+        #[arg(long)]
+        archive_output: bool,
     },
 
     /// Installs the next commit for perf.rust-lang.org
@@ -729,6 +1258,7 @@ fn main_result() -> anyhow::Result<i32> {
             let shared = SharedBenchmarkConfig {
                 artifact_id,
                 toolchain,
+                tags: vec![],
             };
             let config = RuntimeBenchmarkConfig::new(
                 runtime_suite,
@@ -832,10 +1362,36 @@ fn main_result() -> anyhow::Result<i32> {
             db,
             bench_rustc,
             iterations,
+            adaptive_cv_threshold,
+            adaptive_max_runs,
+            parallel_jobs,
+            shuffle_seed,
+            max_duration,
+            stat_transform,
+            criterion_export,
+            json_stdout,
+            measure_resolve_time,
+            stat_aggregation,
+            compiler_invocation_limit,
+            check_perf_counters,
+            measure_rustc_startup,
+            tags,
+            sanitizers,
+            debuginfo_levels,
             self_profile,
             purge,
         } => {
             log_db(&db);
+            if check_perf_counters {
+                #[cfg(unix)]
+                collector::compile::execute::check_perf_counters_supported()?;
+                #[cfg(not(unix))]
+                anyhow::bail!("--check-perf-counters requires `perf` and is only supported on Unix");
+            }
+            let stat_transform = stat_transform
+                .map(|path| StatTransform::from_file(&path))
+                .transpose()?
+                .unwrap_or_default();
             let profiles = opts.profiles.0;
             let scenarios = opts.scenarios.0;
             let backends = opts.codegen_backends.0;
@@ -862,6 +1418,18 @@ fn main_result() -> anyhow::Result<i32> {
                 &local.exclude_suffix,
             )?;
             benchmarks.retain(|b| local.category.0.contains(&b.category()));
+            if let Some(sanitizers) = &sanitizers {
+                benchmarks = benchmarks
+                    .into_iter()
+                    .flat_map(|benchmark| benchmark.into_sanitizer_variants(&sanitizers.0))
+                    .collect();
+            }
+            if !debuginfo_levels.is_empty() {
+                benchmarks = benchmarks
+                    .into_iter()
+                    .flat_map(|benchmark| benchmark.into_debuginfo_variants(&debuginfo_levels))
+                    .collect();
+            }
 
             let artifact_id = ArtifactId::Tag(toolchain.id.clone());
             let mut rt = build_async_runtime();
@@ -871,6 +1439,7 @@ fn main_result() -> anyhow::Result<i32> {
             let shared = SharedBenchmarkConfig {
                 toolchain,
                 artifact_id,
+                tags,
             };
             let config = CompileBenchmarkConfig {
                 benchmarks,
@@ -878,14 +1447,105 @@ fn main_result() -> anyhow::Result<i32> {
                 scenarios,
                 backends,
                 iterations: Some(iterations),
+                adaptive: adaptive_cv_threshold.map(|cv_threshold| AdaptiveRunCount {
+                    cv_threshold,
+                    max_runs: adaptive_max_runs,
+                }),
                 is_self_profile: self_profile.self_profile,
                 bench_rustc: bench_rustc.bench_rustc,
+                shuffle_seed,
+                max_duration: max_duration.map(|minutes| Duration::from_secs(minutes * 60)),
+                stat_transform,
+                criterion_export,
+                json_stdout,
+                measure_resolve_time,
+                stat_aggregation,
+                compiler_invocation_limit,
+                measure_rustc_startup,
+                parallel: (parallel_jobs > 1).then(|| ParallelBenchConfig {
+                    jobs: parallel_jobs,
+                    db: db.db.clone(),
+                }),
             };
 
             run_benchmarks(&mut rt, conn, shared, Some(config), None)?;
             Ok(0)
         }
 
+        Commands::Calibrate {
+            rustc,
+            cargo,
+            benchmark,
+            iterations,
+        } => {
+            let toolchain = get_local_toolchain(
+                &[Profile::Check],
+                &[CodegenBackend::Llvm],
+                &rustc,
+                *ToolchainConfig::default().cargo(cargo.as_deref(), &[]),
+                "",
+                target_triple,
+            )?;
+
+            let mut benchmarks = get_compile_benchmarks(
+                &compile_benchmark_dir,
+                std::slice::from_ref(&benchmark),
+                &[],
+                &[],
+            )?;
+            benchmarks.retain(|b| b.name.0 == benchmark);
+            let Some(benchmark) = benchmarks.pop() else {
+                anyhow::bail!("benchmark `{benchmark}` not found");
+            };
+
+            let rt = build_async_runtime();
+            let mut processor = InMemoryProcessor::new();
+            rt.block_on(benchmark.measure(
+                &mut processor,
+                &[Profile::Check],
+                &[Scenario::Full],
+                &[CodegenBackend::Llvm],
+                &toolchain,
+                Some(iterations),
+                None,
+                None,
+                None,
+            ))
+            .with_context(|| format!("cannot benchmark {}", benchmark.name))?;
+
+            let stats = processor.into_stats();
+            let samples = stats
+                .get(&(Profile::Check, Scenario::Full))
+                .map(|stats| stats.as_slice())
+                .unwrap_or_default();
+            println!(
+                "Noise floor of `{}` (Check + Full, {} iterations):",
+                benchmark.name,
+                samples.len()
+            );
+            for metric in ["instructions:u", "cycles:u", "wall-time"] {
+                let values: Vec<f64> = samples
+                    .iter()
+                    .filter_map(|stats| stats.get(metric))
+                    .collect();
+                if values.len() < 2 {
+                    println!("{metric}: not measured");
+                    continue;
+                }
+                let mean = values.iter().sum::<f64>() / values.len() as f64;
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>()
+                    / (values.len() - 1) as f64;
+                let cv = variance.sqrt() / mean;
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                println!(
+                    "{metric}: CV {:.3}% (mean {mean:.2}, min {min:.2}, max {max:.2})",
+                    cv * 100.0
+                );
+            }
+            Ok(0)
+        }
+
         Commands::BenchNext {
             site_url,
             db,
@@ -975,8 +1635,19 @@ fn main_result() -> anyhow::Result<i32> {
                             scenarios: Scenario::all(),
                             backends: vec![CodegenBackend::Llvm],
                             iterations: runs.map(|v| v as usize),
+                            adaptive: None,
                             is_self_profile: self_profile.self_profile,
                             bench_rustc: bench_rustc.bench_rustc,
+                            shuffle_seed: None,
+                            max_duration: None,
+                            stat_transform: StatTransform::default(),
+                            criterion_export: None,
+                            json_stdout: false,
+                            measure_resolve_time: false,
+                            stat_aggregation: None,
+                            compiler_invocation_limit: None,
+                            measure_rustc_startup: false,
+                            parallel: None,
                         };
                         let runtime_suite = rt.block_on(load_runtime_benchmarks(
                             conn.as_mut(),
@@ -995,6 +1666,7 @@ fn main_result() -> anyhow::Result<i32> {
                         let shared = SharedBenchmarkConfig {
                             artifact_id,
                             toolchain,
+                            tags: vec![],
                         };
 
                         run_benchmarks(
@@ -1036,6 +1708,10 @@ fn main_result() -> anyhow::Result<i32> {
             out_dir,
             rustc2,
             jobs,
+            runs,
+            profile_only_first_iteration,
+            compress_output,
+            archive_output,
         } => {
             let jobs = jobs.max(1);
             if jobs > 1 && !profiler.supports_parallel_execution() {
@@ -1088,6 +1764,10 @@ fn main_result() -> anyhow::Result<i32> {
                         profiles,
                         scenarios,
                         backends,
+                        runs,
+                        profile_only_first_iteration,
+                        compress_output,
+                        archive_output,
                         &mut errors,
                     );
                     Ok(id)
@@ -1598,6 +2278,35 @@ fn run_benchmarks(
         runtime.as_ref(),
     ));
 
+    // Record how many jobserver tokens were in effect for this run, so that
+    // preparation-time differences between machines can be explained.
+    rt.block_on(connection.record_collection_metadata(
+        collector.artifact_row_id,
+        "jobserver-token-count",
+        &jobserver_token_count().to_string(),
+    ));
+
+    // Record the committer date of the rustc-perf checkout, so that locally
+    // collected data can be ordered on a time axis without a separate lookup.
+    // The version (commit hash) recorded with the stats stays unchanged.
+    if let Some(date) = collector::utils::git::get_rustc_perf_commit_date() {
+        rt.block_on(connection.record_collection_metadata(
+            collector.artifact_row_id,
+            "rustc-perf-commit-date",
+            &date,
+        ));
+    }
+
+    // User-supplied experiment tags, stored under `tag:` keys so that many
+    // experiments can be told apart later in one database.
+    for (key, value) in &shared.tags {
+        rt.block_on(connection.record_collection_metadata(
+            collector.artifact_row_id,
+            &format!("tag:{key}"),
+            value,
+        ));
+    }
+
     let start = Instant::now();
 
     // Compile benchmarks
@@ -1666,6 +2375,7 @@ fn bench_published_artifact(
     let shared = SharedBenchmarkConfig {
         artifact_id,
         toolchain,
+        tags: vec![],
     };
     run_benchmarks(
         rt,
@@ -1677,8 +2387,19 @@ fn bench_published_artifact(
             scenarios,
             backends: vec![CodegenBackend::Llvm],
             iterations: Some(3),
+            adaptive: None,
             is_self_profile: false,
             bench_rustc: false,
+            shuffle_seed: None,
+            max_duration: None,
+            stat_transform: StatTransform::default(),
+            criterion_export: None,
+            json_stdout: false,
+            measure_resolve_time: false,
+            stat_aggregation: None,
+            compiler_invocation_limit: None,
+            measure_rustc_startup: false,
+            parallel: None,
         }),
         Some(RuntimeBenchmarkConfig::new(
             runtime_suite,
@@ -1690,7 +2411,7 @@ fn bench_published_artifact(
 
 const COMPILE_BENCHMARK_TIMEOUT: Duration = Duration::from_secs(60 * 30);
 
-async fn with_timeout<F: Future<Output = anyhow::Result<()>>>(fut: F) -> anyhow::Result<()> {
+async fn with_timeout<T, F: Future<Output = anyhow::Result<T>>>(fut: F) -> anyhow::Result<T> {
     match tokio::time::timeout(COMPILE_BENCHMARK_TIMEOUT, fut).await {
         Ok(res) => res,
         Err(_) => Err(anyhow::anyhow!(
@@ -1701,11 +2422,135 @@ async fn with_timeout<F: Future<Output = anyhow::Result<()>>>(fut: F) -> anyhow:
 }
 
 /// Perform compile benchmarks.
+/// Measures the fixed startup overhead of the benchmarked rustc (loading the
+/// sysroot, initializing the compiler) by compiling an empty crate, and
+/// records the result as collection metadata under
+/// `rustc-startup-instructions` and `rustc-startup-wall-time`. This overhead
+/// is part of every per-benchmark measurement, so having it recorded
+/// separately helps to interpret small absolute changes on cheap benchmarks.
+fn measure_rustc_startup(
+    rt: &mut Runtime,
+    conn: &mut dyn Connection,
+    toolchain: &Toolchain,
+    collector: &CollectorCtx,
+) -> anyhow::Result<()> {
+    let dir = tempfile::tempdir().context("cannot create temporary directory")?;
+    fs::write(
+        dir.path().join("Cargo.toml"),
+        "[package]\n\
+         name = \"rustc-startup\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2021\"\n",
+    )?;
+    fs::write(
+        dir.path().join("Cargo.lock"),
+        "# This file is automatically @generated by Cargo.\n\
+         # It is not intended for manual editing.\n\
+         version = 3\n\
+         \n\
+         [[package]]\n\
+         name = \"rustc-startup\"\n\
+         version = \"0.1.0\"\n",
+    )?;
+    fs::create_dir(dir.path().join("src"))?;
+    fs::write(dir.path().join("src").join("lib.rs"), "")?;
+    fs::write(
+        dir.path().join("perf-config.json"),
+        "{\"category\": \"secondary\"}",
+    )?;
+
+    let benchmark = Benchmark::new("rustc-startup".to_string(), dir.path().to_path_buf())?;
+    let mut processor = InMemoryProcessor::new();
+    rt.block_on(benchmark.measure(
+        &mut processor,
+        &[Profile::Check],
+        &[Scenario::Full],
+        &[CodegenBackend::Llvm],
+        toolchain,
+        Some(1),
+        None,
+        None,
+        None,
+    ))?;
+
+    let stats = processor.into_stats();
+    let samples = stats
+        .get(&(Profile::Check, Scenario::Full))
+        .map(|stats| stats.as_slice())
+        .unwrap_or_default();
+    for (stat, key) in [
+        ("instructions:u", "rustc-startup-instructions"),
+        ("wall-time", "rustc-startup-wall-time"),
+    ] {
+        if let Some(value) = samples.iter().find_map(|stats| stats.get(stat)) {
+            rt.block_on(conn.record_collection_metadata(
+                collector.artifact_row_id,
+                key,
+                &value.to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Gathers a small fingerprint of the machine and kernel the collection runs
+/// on, as (metadata key, value) pairs. Statistics collected on different
+/// hardware are not comparable; storing the fingerprint with each collection
+/// lets the dashboard flag cross-machine comparisons. Entries that cannot be
+/// determined on this platform are omitted.
+fn environment_fingerprint() -> Vec<(&'static str, String)> {
+    let mut fingerprint = Vec::new();
+    if let Ok(cores) = std::thread::available_parallelism() {
+        fingerprint.push(("cpu-cores", cores.get().to_string()));
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(model) = fs::read_to_string("/proc/cpuinfo")
+            .ok()
+            .and_then(|cpuinfo| {
+                cpuinfo
+                    .lines()
+                    .find_map(|line| line.strip_prefix("model name"))
+                    .and_then(|rest| rest.split_once(':'))
+                    .map(|(_, model)| model.trim().to_string())
+            })
+        {
+            fingerprint.push(("cpu-model", model));
+        }
+        if let Ok(kernel) = fs::read_to_string("/proc/sys/kernel/osrelease") {
+            fingerprint.push(("kernel", kernel.trim().to_string()));
+        }
+        if let Ok(governor) =
+            fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor")
+        {
+            fingerprint.push(("cpu-governor", governor.trim().to_string()));
+        }
+        // Turbo state: `intel_pstate/no_turbo` is `1` when turbo is disabled,
+        // while the generic `cpufreq/boost` is `1` when boost is enabled.
+        if let Ok(no_turbo) = fs::read_to_string("/sys/devices/system/cpu/intel_pstate/no_turbo") {
+            let state = if no_turbo.trim() == "1" {
+                "disabled"
+            } else {
+                "enabled"
+            };
+            fingerprint.push(("cpu-turbo", state.to_string()));
+        } else if let Ok(boost) = fs::read_to_string("/sys/devices/system/cpu/cpufreq/boost") {
+            let state = if boost.trim() == "1" {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            fingerprint.push(("cpu-turbo", state.to_string()));
+        }
+    }
+    fingerprint
+}
+
 fn bench_compile(
     rt: &mut Runtime,
     conn: &mut dyn Connection,
     shared: &SharedBenchmarkConfig,
-    config: CompileBenchmarkConfig,
+    mut config: CompileBenchmarkConfig,
     collector: &CollectorCtx,
 ) -> BenchmarkErrors {
     let mut errors = BenchmarkErrors::new();
@@ -1714,19 +2559,191 @@ fn bench_compile(
         shared.artifact_id, shared.toolchain.triple
     );
 
+    // Fail early if the kernel forbids unprivileged access to the hardware
+    // counters: `perf stat` would report `<not supported>` for every counter
+    // and the whole (possibly hours-long) collection would record nothing.
+    #[cfg(target_os = "linux")]
+    if let Err(error) = collector::compile::execute::check_perf_event_paranoid() {
+        eprintln!("collector error: {error:#}");
+        errors.incr();
+        return errors;
+    }
+
+    // Fail early if `--keep-going` was requested, but the used cargo does not
+    // support it, instead of failing on each benchmark below.
+    if std::env::var_os("CARGO_KEEP_GOING").is_some() {
+        if let Err(error) = check_keep_going_supported(&shared.toolchain.components.cargo) {
+            eprintln!("collector error: {error:#}");
+            errors.incr();
+            return errors;
+        }
+    }
+
+    // Fail early if measuring under a cgroup was requested, but the cgroup is
+    // missing or not delegated, instead of failing on every rustc invocation.
+    // The configured limits are recorded as metadata, so that results taken
+    // under a limit remain interpretable later.
+    #[cfg(target_os = "linux")]
+    let cgroup_memory_events_start = match std::env::var_os(cgroup::CGROUP_ENV_VAR) {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if let Err(error) = cgroup::check_cgroup_delegation(&path) {
+                eprintln!("collector error: {error:#}");
+                errors.incr();
+                return errors;
+            }
+            rt.block_on(conn.record_collection_metadata(
+                collector.artifact_row_id,
+                "cgroup:path",
+                &path.display().to_string(),
+            ));
+            for (name, value) in cgroup::read_cgroup_limits(&path) {
+                rt.block_on(conn.record_collection_metadata(
+                    collector.artifact_row_id,
+                    &format!("cgroup:{name}"),
+                    &value,
+                ));
+            }
+            cgroup::read_cgroup_memory_events(&path).map(|events| (path, events))
+        }
+        None => None,
+    };
+
+    // Record whether ASLR was disabled for the measured compilations
+    // (`RUSTC_PERF_DISABLE_ASLR`, honored by `rustc-fake` on Linux), so that
+    // analysts later know under which measurement conditions the results were
+    // taken. Other platforms have no equivalent of `ADDR_NO_RANDOMIZE`.
+    let aslr = if std::env::var_os("RUSTC_PERF_DISABLE_ASLR").is_some() {
+        if cfg!(target_os = "linux") {
+            "disabled"
+        } else {
+            eprintln!(
+                "collector error: RUSTC_PERF_DISABLE_ASLR is not supported on this platform; \
+                 ASLR remains enabled"
+            );
+            "not supported"
+        }
+    } else {
+        "enabled"
+    };
+    rt.block_on(conn.record_collection_metadata(collector.artifact_row_id, "aslr", aslr));
+
+    // Record what machine produced these statistics, so that the dashboard
+    // can flag comparisons between collections from different hardware.
+    for (key, value) in environment_fingerprint() {
+        rt.block_on(conn.record_collection_metadata(collector.artifact_row_id, key, &value));
+    }
+
+    // Record the IncrPatched application order when an alternate one was
+    // requested (`RUSTC_PERF_PATCH_ORDER`), so that order-sensitive results
+    // can be told apart from runs with the default ordering.
+    if let Ok(order) = std::env::var("RUSTC_PERF_PATCH_ORDER") {
+        rt.block_on(conn.record_collection_metadata(
+            collector.artifact_row_id,
+            "patch-order",
+            &order,
+        ));
+    }
+
+    // Shuffle the execution order if a seed was provided, and record the seed,
+    // so that the ordering of a suspicious result can be reconstructed.
+    if let Some(seed) = config.shuffle_seed {
+        shuffle_benchmarks(&mut config.benchmarks, seed);
+        rt.block_on(conn.record_collection_metadata(
+            collector.artifact_row_id,
+            "benchmark-shuffle-seed",
+            &seed.to_string(),
+        ));
+    }
+
     let bench_rustc = config.bench_rustc;
 
+    // A hard budget on measured rustc invocations (used for smoke tests).
+    // The budget is enforced globally in `run_rustc`; the check below only
+    // stops the benchmark loop early once it has been used up.
+    if let Some(limit) = config.compiler_invocation_limit {
+        set_compiler_invocation_limit(limit);
+    }
+
     let start = Instant::now();
 
+    // Establish the startup overhead baseline before any real benchmark runs,
+    // so that it is recorded even if the collection is interrupted later.
+    if config.measure_rustc_startup {
+        if let Err(error) = measure_rustc_startup(rt, conn, &shared.toolchain, collector) {
+            eprintln!("collector error: failed to measure rustc startup overhead: {error:#}");
+        }
+    }
+
+    // Shared preparation state for benchmarks that declare a `group` in their
+    // perf-config.json. Kept alive for the whole collection.
+    let group_cache = GroupPreparationCache::default();
+
+    // Time dependency resolution separately from compilation, once per
+    // benchmark. This helps to diagnose slow-start complaints that are not
+    // actually about the compiler.
+    if config.measure_resolve_time {
+        for benchmark in &config.benchmarks {
+            match benchmark.time_cargo_metadata(&shared.toolchain) {
+                Ok(duration) => {
+                    rt.block_on(conn.record_collection_metadata(
+                        collector.artifact_row_id,
+                        &format!("resolve-wall-time:{}", benchmark.name),
+                        &duration.as_secs_f64().to_string(),
+                    ));
+                }
+                Err(error) => {
+                    eprintln!(
+                        "collector error: failed to time `cargo metadata` for '{}': {:#}",
+                        benchmark.name, error
+                    );
+                }
+            }
+        }
+    }
+
+    // The known-incompatible cache (`RUSTC_PERF_INCOMPATIBLE_CACHE`) skips
+    // benchmarks that previously failed to build on a similar compiler, which
+    // mainly saves time when bisecting across many old commits.
+    let mut incompatible_cache = match incompatible::KnownIncompatibleCache::from_env() {
+        Some(Ok(cache)) => Some(cache),
+        Some(Err(error)) => {
+            eprintln!("collector error: {error:#}");
+            errors.incr();
+            return errors;
+        }
+        None => None,
+    };
+    let toolchain_version_key = incompatible_cache
+        .as_ref()
+        .and_then(|_| incompatible::version_key(&shared.toolchain));
+
+    let mut junit = JunitReporter::from_env();
+    if junit.is_some() && config.parallel.is_some() {
+        eprintln!(
+            "RUSTC_PERF_JUNIT only covers serially executed benchmarks and is \
+             not supported with parallel benchmarking; no report will be written"
+        );
+        junit = None;
+    }
+
     let mut measure_and_record =
         |benchmark_name: &BenchmarkName,
          category: Category,
+         junit: &mut Option<JunitReporter>,
          print_intro: &dyn Fn(),
          measure: &dyn Fn(&mut BenchProcessor) -> anyhow::Result<()>| {
             let is_fresh = rt.block_on(collector.start_compile_step(conn, benchmark_name));
             if !is_fresh {
                 eprintln!("skipping {} -- already benchmarked", benchmark_name);
-                return;
+                if let Some(junit) = junit {
+                    junit.record(
+                        benchmark_name,
+                        JunitOutcome::Skipped("already benchmarked".to_string()),
+                        Duration::ZERO,
+                    );
+                }
+                return false;
             }
             let mut tx = rt.block_on(conn.transaction());
             let (supports_stable, category) = category.db_representation();
@@ -1743,9 +2760,23 @@ fn bench_compile(
                 &shared.artifact_id,
                 collector.artifact_row_id,
                 config.is_self_profile,
+                config.stat_transform.clone(),
+                config.criterion_export.clone(),
+                config.stat_aggregation,
+                config.json_stdout,
             );
+            let measure_start = Instant::now();
             let result = measure(&mut processor);
+            if let Some(junit) = junit {
+                let outcome = match &result {
+                    Ok(()) => JunitOutcome::Passed,
+                    Err(error) => JunitOutcome::Failed(format!("{:#}", error)),
+                };
+                junit.record(benchmark_name, outcome, measure_start.elapsed());
+            }
+            let mut build_failed = false;
             if let Err(s) = result {
+                build_failed = incompatible::is_build_failure(&s);
                 eprintln!(
                     "collector error: Failed to benchmark '{}', recorded: {:#}",
                     benchmark_name, s
@@ -1759,31 +2790,95 @@ fn bench_compile(
             };
             rt.block_on(collector.end_compile_step(tx.conn(), benchmark_name));
             rt.block_on(tx.commit()).expect("committed");
+            build_failed
         };
 
     // Normal benchmarks.
-    for (nth_benchmark, benchmark) in config.benchmarks.iter().enumerate() {
-        measure_and_record(
-            &benchmark.name,
-            benchmark.category(),
-            &|| {
+    let mut parallel_errors = 0;
+    if let Some(parallel) = &config.parallel {
+        parallel_errors = bench_compile_parallel(shared, &config, collector, parallel);
+    } else {
+        let mut progress = ProgressReporter::new(config.benchmarks.len());
+        for (nth_benchmark, benchmark) in config.benchmarks.iter().enumerate() {
+            // Stop gracefully if the wall-clock budget has been exceeded, instead
+            // of being hard-killed mid-benchmark by an external time limit. The
+            // skipped benchmarks will be picked up when the collection is resumed.
+            if let Some(max_duration) = config.max_duration {
+                if start.elapsed() >= max_duration {
+                    eprintln!(
+                        "stopping collection after {:?}: the time budget of {:?} was exceeded \
+                         with {} benchmark(s) remaining",
+                        start.elapsed(),
+                        max_duration,
+                        config.benchmarks.len() - nth_benchmark
+                    );
+                    break;
+                }
+            }
+            if config.compiler_invocation_limit.is_some() && compiler_invocation_budget_exhausted() {
                 eprintln!(
-                    "{}",
-                    n_normal_benchmarks_remaining(config.benchmarks.len() - nth_benchmark)
-                )
-            },
-            &|processor| {
-                rt.block_on(with_timeout(benchmark.measure(
-                    processor,
-                    &config.profiles,
-                    &config.scenarios,
-                    &config.backends,
-                    &shared.toolchain,
-                    config.iterations,
-                )))
-                .with_context(|| anyhow::anyhow!("Cannot compile {}", benchmark.name))
-            },
-        )
+                    "stopping collection after {} compiler invocations: the invocation budget \
+                     was exhausted with {} benchmark(s) remaining",
+                    compiler_invocation_count(),
+                    config.benchmarks.len() - nth_benchmark
+                );
+                break;
+            }
+            if let (Some(cache), Some(version)) = (&incompatible_cache, &toolchain_version_key) {
+                if cache.is_incompatible(&benchmark.name.0, version) {
+                    eprintln!(
+                        "skipping {} -- known to fail to build on rustc {} \
+                         (clear the RUSTC_PERF_INCOMPATIBLE_CACHE file to retry)",
+                        benchmark.name, version
+                    );
+                    if let Some(junit) = &mut junit {
+                        junit.record(
+                            &benchmark.name,
+                            JunitOutcome::Skipped(format!(
+                                "known to fail to build on rustc {version}"
+                            )),
+                            Duration::ZERO,
+                        );
+                    }
+                    continue;
+                }
+            }
+            // Results for a cross-compilation target are recorded under a
+            // name tagged with the triple, so they never collide with host
+            // measurements of the same benchmark.
+            let recorded_name = match cross_target() {
+                Some(target) => BenchmarkName(format!("{}@{}", benchmark.name, target)),
+                None => benchmark.name.clone(),
+            };
+            let build_failed = measure_and_record(
+                &recorded_name,
+                benchmark.category(),
+                &mut junit,
+                &|| eprintln!("{}", progress.intro(nth_benchmark + 1, &benchmark.name)),
+                &|processor| {
+                    rt.block_on(with_timeout(benchmark.measure(
+                        processor,
+                        &config.profiles,
+                        &config.scenarios,
+                        &config.backends,
+                        &shared.toolchain,
+                        config.iterations,
+                        config.adaptive,
+                        Some(&group_cache),
+                        None,
+                    )))
+                    .map(|_| ())
+                    .with_context(|| anyhow::anyhow!("Cannot compile {}", benchmark.name))
+                },
+            );
+            if build_failed {
+                if let (Some(cache), Some(version)) = (&mut incompatible_cache, &toolchain_version_key)
+                {
+                    cache.record(&benchmark.name.0, version);
+                }
+            }
+            progress.finish();
+        }
     }
 
     // The special rustc benchmark, if requested.
@@ -1791,6 +2886,7 @@ fn bench_compile(
         measure_and_record(
             &BenchmarkName("rustc".to_string()),
             Category::Primary,
+            &mut junit,
             &|| eprintln!("Special benchmark commencing (due to `--bench-rustc`)"),
             &|processor| {
                 rt.block_on(with_timeout(processor.measure_rustc(&shared.toolchain)))
@@ -1799,6 +2895,32 @@ fn bench_compile(
         );
     }
 
+    // Record whether the cgroup limits were actually hit during this run, by
+    // comparing the cumulative `memory.events` counters before and after.
+    #[cfg(target_os = "linux")]
+    if let Some((path, (max_before, oom_before))) = cgroup_memory_events_start {
+        if let Some((max_after, oom_after)) = cgroup::read_cgroup_memory_events(&path) {
+            rt.block_on(conn.record_collection_metadata(
+                collector.artifact_row_id,
+                "cgroup:memory-limit-hits",
+                &max_after.saturating_sub(max_before).to_string(),
+            ));
+            rt.block_on(conn.record_collection_metadata(
+                collector.artifact_row_id,
+                "cgroup:oom-kills",
+                &oom_after.saturating_sub(oom_before).to_string(),
+            ));
+        }
+    }
+
+    if let Some(junit) = &junit {
+        if let Err(error) = junit.write() {
+            eprintln!("collector error: failed to write JUnit report: {:#}", error);
+        }
+    }
+
+    errors.0 += parallel_errors;
+
     let end = start.elapsed();
 
     eprintln!(
@@ -1813,6 +2935,161 @@ fn bench_compile(
     errors
 }
 
+/// Runs the normal compile benchmarks on a bounded worker pool instead of
+/// sequentially. Each worker opens its own database connection (and async
+/// runtime) and pulls the next benchmark from a shared queue;
+/// `start_compile_step` already skips benchmarks that were measured
+/// elsewhere, so workers do not collide. Group preparation sharing and the
+/// known-incompatible cache are per-worker in this mode (each worker loads
+/// and saves its own copy of the cache file, so concurrent saves can lose
+/// each other's new entries — only costing a retry on the next run). The
+/// `--max-duration` and compiler-invocation budgets stop each worker before
+/// it claims another benchmark, like on the serial path. Returns the number
+/// of failed benchmarks.
+fn bench_compile_parallel(
+    shared: &SharedBenchmarkConfig,
+    config: &CompileBenchmarkConfig,
+    collector: &CollectorCtx,
+    parallel: &ParallelBenchConfig,
+) -> usize {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let start = Instant::now();
+    let next = AtomicUsize::new(0);
+    let errors = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..parallel.jobs {
+            scope.spawn(|| {
+                let rt = build_async_runtime();
+                let pool = database::Pool::open(&parallel.db);
+                let mut conn = rt.block_on(pool.connection());
+                let group_cache = GroupPreparationCache::default();
+                let mut incompatible_cache = match incompatible::KnownIncompatibleCache::from_env()
+                {
+                    Some(Ok(cache)) => Some(cache),
+                    Some(Err(error)) => {
+                        eprintln!("collector error: {error:#}");
+                        None
+                    }
+                    None => None,
+                };
+                let toolchain_version_key = incompatible_cache
+                    .as_ref()
+                    .and_then(|_| incompatible::version_key(&shared.toolchain));
+                loop {
+                    // See `bench_compile`: stop gracefully once the
+                    // wall-clock or compiler-invocation budget is exhausted;
+                    // the remaining benchmarks will be picked up when the
+                    // collection is resumed.
+                    if let Some(max_duration) = config.max_duration {
+                        if start.elapsed() >= max_duration {
+                            eprintln!(
+                                "stopping worker after {:?}: the time budget of {:?} was exceeded",
+                                start.elapsed(),
+                                max_duration
+                            );
+                            break;
+                        }
+                    }
+                    if config.compiler_invocation_limit.is_some()
+                        && compiler_invocation_budget_exhausted()
+                    {
+                        eprintln!(
+                            "stopping worker after {} compiler invocations: the invocation \
+                             budget was exhausted",
+                            compiler_invocation_count()
+                        );
+                        break;
+                    }
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(benchmark) = config.benchmarks.get(index) else {
+                        break;
+                    };
+                    if let (Some(cache), Some(version)) =
+                        (&incompatible_cache, &toolchain_version_key)
+                    {
+                        if cache.is_incompatible(&benchmark.name.0, version) {
+                            eprintln!(
+                                "skipping {} -- known to fail to build on rustc {} \
+                                 (clear the RUSTC_PERF_INCOMPATIBLE_CACHE file to retry)",
+                                benchmark.name, version
+                            );
+                            continue;
+                        }
+                    }
+                    // See `bench_compile`: cross-target results are recorded
+                    // under a name tagged with the triple.
+                    let recorded_name = match cross_target() {
+                        Some(target) => BenchmarkName(format!("{}@{}", benchmark.name, target)),
+                        None => benchmark.name.clone(),
+                    };
+                    let is_fresh =
+                        rt.block_on(collector.start_compile_step(conn.as_ref(), &recorded_name));
+                    if !is_fresh {
+                        eprintln!("skipping {} -- already benchmarked", recorded_name);
+                        continue;
+                    }
+                    let mut tx = rt.block_on(conn.transaction());
+                    let (supports_stable, category) = benchmark.category().db_representation();
+                    rt.block_on(tx.conn().record_compile_benchmark(
+                        &recorded_name.0,
+                        Some(supports_stable),
+                        category,
+                    ));
+                    eprintln!("Benchmarking {}", recorded_name);
+                    let mut processor = BenchProcessor::new(
+                        tx.conn(),
+                        &recorded_name,
+                        &shared.artifact_id,
+                        collector.artifact_row_id,
+                        config.is_self_profile,
+                        config.stat_transform.clone(),
+                        config.criterion_export.clone(),
+                        config.stat_aggregation,
+                        config.json_stdout,
+                    );
+                    let result = rt
+                        .block_on(with_timeout(benchmark.measure(
+                            &mut processor,
+                            &config.profiles,
+                            &config.scenarios,
+                            &config.backends,
+                            &shared.toolchain,
+                            config.iterations,
+                            config.adaptive,
+                            Some(&group_cache),
+                            None,
+                        )))
+                        .map(|_| ())
+                        .with_context(|| anyhow::anyhow!("Cannot compile {}", benchmark.name));
+                    if let Err(s) = result {
+                        if incompatible::is_build_failure(&s) {
+                            if let (Some(cache), Some(version)) =
+                                (&mut incompatible_cache, &toolchain_version_key)
+                            {
+                                cache.record(&benchmark.name.0, version);
+                            }
+                        }
+                        eprintln!(
+                            "collector error: Failed to benchmark '{}', recorded: {:#}",
+                            recorded_name, s
+                        );
+                        errors.fetch_add(1, Ordering::SeqCst);
+                        rt.block_on(tx.conn().record_error(
+                            collector.artifact_row_id,
+                            &recorded_name.0,
+                            &format!("{:?}", s),
+                        ));
+                    }
+                    rt.block_on(collector.end_compile_step(tx.conn(), &recorded_name));
+                    rt.block_on(tx.commit()).expect("committed");
+                }
+            });
+        }
+    });
+    errors.load(Ordering::SeqCst)
+}
+
 /// Records the sizes of individual components (rustc, libLLVM, etc.) for the given toolchain
 /// and artifact id into the database.
 async fn record_toolchain_sizes(
@@ -0,0 +1,32 @@
+//! A shim that stands in for the linker during benchmarking, so that the wall
+//! time of the link step can be measured separately from the rest of the
+//! compilation. `rustc-fake` points rustc at this binary via `-Clinker` and
+//! collects the recorded time afterwards (see `!link-wall-time:`).
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process::Command;
+use std::time::Instant;
+
+fn main() {
+    let args = env::args_os().skip(1).collect::<Vec<_>>();
+    let linker = env::var_os("LINKER_REAL").unwrap_or_else(|| "cc".into());
+
+    let start = Instant::now();
+    let status = Command::new(&linker)
+        .args(&args)
+        .status()
+        .expect("failed to spawn linker");
+    let elapsed = start.elapsed();
+
+    // rustc can link multiple times per invocation (e.g. multiple binaries),
+    // so append rather than overwrite; `rustc-fake` sums the entries.
+    if let Some(file) = env::var_os("LINK_WALL_TIME_FILE") {
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(file) {
+            let _ = writeln!(file, "{}", elapsed.as_secs_f64());
+        }
+    }
+
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -94,9 +94,90 @@ fn main() {
 
         benchlib::process::raise_process_priority();
 
+        // Optionally run the measured compilation inside a pre-configured
+        // cgroup v2, so that it executes under the memory/CPU limits set on
+        // it (see `collector::utils::cgroup`).
+        #[cfg(target_os = "linux")]
+        enter_cgroup();
+
+        // Optionally disable ASLR for the measured compilation, reducing
+        // run-to-run noise in cycle/instruction counts of short compiles.
+        #[cfg(target_os = "linux")]
+        disable_aslr();
+
+        // Optionally pin the measured compilation to a fixed set of CPU
+        // cores, so the scheduler cannot migrate it between cores with
+        // different cache/turbo states.
+        #[cfg(target_os = "linux")]
+        pin_to_cpu_list();
+
         // These strings come from `PerfTool::name()`.
         match wrapper {
             "PerfStat" | "PerfStatSelfProfile" => {
+                print_crate_metadata(&args);
+
+                // Optionally measure the wall time of the link step
+                // separately, by wrapping the linker with `linker-fake`.
+                // rustdoc does not accept `-Clinker` (and does not link).
+                #[cfg(unix)]
+                let link_wall_time_file = if actually_rustdoc {
+                    None
+                } else {
+                    setup_link_wall_time(&mut args)
+                };
+
+                // Optionally read the counters in-process through
+                // `perf_event_open`, which avoids the fixed overhead of
+                // spawning a `perf stat` subprocess that can dominate the
+                // measurement of very short compilations. Only used for plain
+                // benchmarking runs; self-profile runs keep using `perf stat`.
+                #[cfg(target_os = "linux")]
+                if wrapper == "PerfStat" && env::var_os("RUSTC_PERF_PMC").is_some() {
+                    match pmc::Counters::start() {
+                        Some(counters) => {
+                            let mut cmd = Command::new("setarch");
+                            cmd.arg(std::env::consts::ARCH)
+                                .arg("-R")
+                                .arg(&tool)
+                                .args(&args);
+
+                            let start = Instant::now();
+                            run_with_determinism_env(cmd);
+                            let dur = start.elapsed();
+                            counters.report();
+                            if let Some(file) = &link_wall_time_file {
+                                print_link_wall_time(file);
+                            }
+                            print_memory();
+                            print_time(dur);
+                            return;
+                        }
+                        None => {
+                            // Typically a too restrictive
+                            // /proc/sys/kernel/perf_event_paranoid setting.
+                            eprintln!(
+                                "cannot open perf counters directly, \
+                                 falling back to `perf stat` \
+                                 (check /proc/sys/kernel/perf_event_paranoid)"
+                            );
+                        }
+                    }
+                }
+
+                // The baseline event set (keep in sync with `PERF_COUNTERS`
+                // in `collector::compile::execute`), optionally extended with
+                // the comma-separated events of `RUSTC_PERF_EXTRA_PERF_EVENTS`
+                // (e.g. `cache-references,branch-instructions`).
+                let mut events = String::from(
+                    "instructions:u,cycles:u,task-clock,cpu-clock,faults,context-switches,branch-misses,cache-misses",
+                );
+                if let Ok(extra) = env::var("RUSTC_PERF_EXTRA_PERF_EVENTS") {
+                    if !extra.is_empty() {
+                        events.push(',');
+                        events.push_str(&extra);
+                    }
+                }
+
                 let mut cmd = Command::new("perf");
                 let has_perf = cmd.output().is_ok();
                 assert!(has_perf);
@@ -107,7 +188,7 @@ fn main() {
                     .env("LC_NUMERIC", "C")
                     .arg("-x;")
                     .arg("-e")
-                    .arg("instructions:u,cycles:u,task-clock,cpu-clock,faults,context-switches,branch-misses,cache-misses")
+                    .arg(&events)
                     .arg("--log-fd")
                     .arg("1")
                     .arg("setarch")
@@ -129,6 +210,10 @@ fn main() {
                 let start = Instant::now();
                 run_with_determinism_env(cmd);
                 let dur = start.elapsed();
+                #[cfg(unix)]
+                if let Some(file) = &link_wall_time_file {
+                    print_link_wall_time(file);
+                }
                 print_memory();
                 print_time(dur);
                 if wrapper == "PerfStatSelfProfile" {
@@ -136,7 +221,25 @@ fn main() {
                 }
             }
 
+            "TimeStat" => {
+                print_crate_metadata(&args);
+
+                // Reduced measurement used on macOS, where `perf` is not
+                // available: wall-time via plain timing and max-rss via
+                // getrusage. No hardware counters are collected.
+                let mut cmd = Command::new(&tool);
+                cmd.args(&args);
+
+                let start = Instant::now();
+                run_with_determinism_env(cmd);
+                let dur = start.elapsed();
+                print_memory();
+                print_time(dur);
+            }
+
             "XperfStat" | "XperfStatSelfProfile" => {
+                print_crate_metadata(&args);
+
                 // For Windows, we use a combination of xperf and tracelog to capture ETW events
                 // including hardware performance counters. To do this, we start an ETW trace using
                 // tracelog, telling it to include the InstructionRetired and TotalCycles PMCs for
@@ -351,6 +454,15 @@ fn main() {
                 run_with_determinism_env(cmd);
             }
 
+            "Heaptrack" => {
+                let mut cmd = Command::new("heaptrack");
+                let has_heaptrack = cmd.output().is_ok();
+                assert!(has_heaptrack);
+                cmd.arg("--record-only").arg(&tool).args(&args);
+
+                run_with_determinism_env(cmd);
+            }
+
             "Bytehound" => {
                 let mut cmd = Command::new(tool);
                 cmd.args(args);
@@ -435,6 +547,28 @@ fn main() {
             }
         }
 
+        // When the collector asked for build-script timings, build scripts
+        // (which are never wrapped) are compiled under a timer, and the
+        // emitted binary is replaced with a shim that times its execution
+        // (see `build-script-fake`).
+        #[cfg(unix)]
+        if env::var_os("BUILD_SCRIPT_TIMES_FILE").is_some()
+            && args
+                .windows(2)
+                .any(|args| args[0] == "--crate-name" && args[1] == "build_script_build")
+        {
+            let mut cmd = Command::new(&tool);
+            determinism_env(&mut cmd);
+            cmd.args(&args);
+            let start = Instant::now();
+            let status = cmd.status().expect("failed to spawn");
+            if status.success() {
+                record_build_script_time("compile", start.elapsed());
+                install_build_script_shim(&args);
+            }
+            std::process::exit(status.code().unwrap_or(1));
+        }
+
         let mut cmd = Command::new(&tool);
         determinism_env(&mut cmd);
         cmd.args(&args);
@@ -442,6 +576,80 @@ fn main() {
     }
 }
 
+/// Appends one `<kind>:<seconds>` entry to the file named by
+/// `BUILD_SCRIPT_TIMES_FILE`. The collector sums the entries per kind after
+/// the measured cargo invocation and records them as `build-script-compile` /
+/// `build-script-run` stats.
+#[cfg(unix)]
+fn record_build_script_time(kind: &str, elapsed: Duration) {
+    use std::io::Write;
+    if let Some(file) = env::var_os("BUILD_SCRIPT_TIMES_FILE") {
+        if let Ok(mut file) = fs::OpenOptions::new().create(true).append(true).open(file) {
+            let _ = writeln!(file, "{}:{}", kind, elapsed.as_secs_f64());
+        }
+    }
+}
+
+/// Replaces the binary emitted for a `build_script_build` crate with a shell
+/// script that runs it through the `build-script-fake` shim, so that the wall
+/// time of executing `build.rs` is recorded separately from compiling it.
+/// Cargo hardlinks the emitted file to `build/<pkg>/build-script-build` and
+/// runs that, so replacing the file here wraps every subsequent execution.
+/// Best-effort: if anything is missing, the build script simply runs untimed.
+#[cfg(unix)]
+fn install_build_script_shim(args: &[OsString]) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Some(fake) = env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name("build-script-fake"))
+        .filter(|fake| fake.is_file())
+    else {
+        eprintln!("BUILD_SCRIPT_TIMES_FILE is set, but build-script-fake does not exist");
+        return;
+    };
+    // Cargo passes codegen options both as `-C <opt>` and `-C<opt>`.
+    let extra_filename = args
+        .iter()
+        .filter_map(|arg| arg.to_str())
+        .find_map(|arg| arg.strip_prefix("-Cextra-filename="))
+        .or_else(|| {
+            args.windows(2).find_map(|args| {
+                if args[0] == "-C" {
+                    args[1].to_str()?.strip_prefix("extra-filename=")
+                } else {
+                    None
+                }
+            })
+        })
+        .unwrap_or("");
+    let Some(out_dir) = args
+        .windows(2)
+        .find(|args| args[0] == "--out-dir")
+        .and_then(|args| args[1].to_str())
+    else {
+        return;
+    };
+    let binary = PathBuf::from(out_dir).join(format!("build_script_build{extra_filename}"));
+    if !binary.is_file() {
+        return;
+    }
+    let real = binary.with_file_name(format!("build_script_build{extra_filename}-real"));
+    if fs::rename(&binary, &real).is_err() {
+        return;
+    }
+    let script = format!(
+        "#!/bin/sh\nexec \"{}\" \"{}\" \"$@\"\n",
+        fake.display(),
+        real.display()
+    );
+    if fs::write(&binary, script).is_err() {
+        let _ = fs::rename(&real, &binary);
+        return;
+    }
+    let _ = fs::set_permissions(&binary, fs::Permissions::from_mode(0o755));
+}
+
 fn process_self_profile_output(prof_out_dir: PathBuf, args: &[OsString]) {
     let crate_name = args
         .windows(2)
@@ -469,6 +677,10 @@ fn exec(cmd: &mut Command) -> ! {
     panic!("failed to exec `{}`: {}", cmd_d, error);
 }
 
+/// Reports the peak resident set size of the compilation (in kilobytes) in
+/// the same `perf stat -x;` line format as the hardware counters, so that
+/// `process_stat_output` stores it as the `max-rss` stat alongside the other
+/// measurements.
 #[cfg(unix)]
 fn print_memory() {
     use std::mem;
@@ -477,10 +689,129 @@ fn print_memory() {
         let mut usage = mem::zeroed();
         let r = libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage);
         if r == 0 {
+            // `ru_maxrss` is in kilobytes on Linux but in bytes on macOS;
+            // normalize so the `max-rss` stat is comparable across platforms.
+            let max_rss = if cfg!(target_os = "macos") {
+                usage.ru_maxrss / 1024
+            } else {
+                usage.ru_maxrss
+            };
             // for explanation of all the semicolons, see `print_time` below
-            println!("{};;max-rss;3;100.00", usage.ru_maxrss);
+            println!("{};;max-rss;3;100.00", max_rss);
+        }
+    }
+}
+
+/// Moves this process — and therefore all of its children, including the
+/// measured rustc — into the cgroup v2 named by `RUSTC_PERF_CGROUP`, so that
+/// the compilation runs under the memory/CPU limits configured on it.
+/// The cgroup must already exist and be delegated to the current user; the
+/// collector checks both once at startup, but the write can still fail here
+/// (e.g. if the cgroup was removed mid-run), in which case failing loudly is
+/// better than silently measuring without the limits.
+#[cfg(target_os = "linux")]
+fn enter_cgroup() {
+    let Some(cgroup) = env::var_os("RUSTC_PERF_CGROUP") else {
+        return;
+    };
+    let procs = PathBuf::from(&cgroup).join("cgroup.procs");
+    if let Err(error) = fs::write(&procs, std::process::id().to_string()) {
+        panic!(
+            "cannot move process into cgroup `{}`: {:?}\n\
+             the cgroup must exist and be delegated to the current user \
+             (e.g. `sudo chown -R $USER {}`)",
+            cgroup.to_string_lossy(),
+            error,
+            cgroup.to_string_lossy(),
+        );
+    }
+}
+
+/// Disables address-space layout randomization for this process when
+/// `RUSTC_PERF_DISABLE_ASLR` is set. The `ADDR_NO_RANDOMIZE` personality flag
+/// persists across `exec`, so the spawned perf tool and the measured rustc
+/// inherit it — the same effect as running under `setarch -R`.
+#[cfg(target_os = "linux")]
+fn disable_aslr() {
+    if env::var_os("RUSTC_PERF_DISABLE_ASLR").is_none() {
+        return;
+    }
+    unsafe {
+        // Passing an invalid persona returns the current one unchanged.
+        let current = libc::personality(0xffff_ffff);
+        if current == -1
+            || libc::personality(current as libc::c_ulong | libc::ADDR_NO_RANDOMIZE as libc::c_ulong)
+                == -1
+        {
+            panic!(
+                "cannot disable ASLR: {:?}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Pins this process (and, through inheritance, the spawned perf tool and the
+/// measured rustc) to the CPUs named by the `RUSTC_PERF_CPU_LIST` environment
+/// variable — the same effect as running under `taskset -c`. The affinity
+/// survives `exec` and is inherited by children, and only `rustc-fake` sets
+/// it, so the parallel preparation phase is unaffected. The list uses the
+/// usual cpulist syntax, e.g. `0-3` or `0,2,4-6`.
+#[cfg(target_os = "linux")]
+fn pin_to_cpu_list() {
+    let Ok(list) = env::var("RUSTC_PERF_CPU_LIST") else {
+        return;
+    };
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for part in list.split(',') {
+        let part = part.trim();
+        let range = match part.split_once('-') {
+            Some((start, end)) => (start.parse::<usize>(), end.parse::<usize>()),
+            None => (part.parse::<usize>(), part.parse::<usize>()),
+        };
+        let (Ok(start), Ok(end)) = range else {
+            panic!("invalid RUSTC_PERF_CPU_LIST entry `{}`", part);
+        };
+        for cpu in start..=end {
+            unsafe { libc::CPU_SET(cpu, &mut set) };
         }
     }
+    let result =
+        unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+    if result == -1 {
+        panic!(
+            "cannot pin to CPUs `{}`: {:?}",
+            list,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Prints the `-Cmetadata` value(s) that cargo passed for the final crate as
+/// a `!crate-metadata:` marker. rustc mixes these values into the stable crate
+/// id and the `-Cextra-filename` hash, so they give the emitted artifact a
+/// canonical identity that is stable across runs with identical inputs
+/// (crate name, version, features, compiler), unlike path-dependent hashes.
+/// The collector stores the value as collection metadata.
+fn print_crate_metadata(args: &[OsString]) {
+    let mut values = Vec::new();
+    let mut prev_was_c = false;
+    for arg in args {
+        let Some(arg) = arg.to_str() else { continue };
+        if prev_was_c {
+            if let Some(value) = arg.strip_prefix("metadata=") {
+                values.push(value);
+            }
+            prev_was_c = false;
+        } else if arg == "-C" {
+            prev_was_c = true;
+        } else if let Some(value) = arg.strip_prefix("-Cmetadata=") {
+            values.push(value);
+        }
+    }
+    if !values.is_empty() {
+        println!("!crate-metadata:{}", values.join(","));
+    }
 }
 
 fn print_time(dur: Duration) {
@@ -499,3 +830,178 @@ fn print_time(dur: Duration) {
 
 #[cfg(windows)]
 fn print_memory() {}
+
+/// When `RUSTC_PERF_LINK_WALL_TIME` is set, wraps the linker invoked by rustc
+/// with the `linker-fake` shim, which times the real linker (`cc` by default,
+/// overridable via `LINKER_REAL`) and records the elapsed wall time into the
+/// returned file. Compilations that do not link (e.g. check builds or pure
+/// libraries) never invoke the linker, so no `!link-wall-time:` marker is
+/// emitted for them.
+#[cfg(unix)]
+fn setup_link_wall_time(args: &mut Vec<OsString>) -> Option<PathBuf> {
+    if env::var_os("RUSTC_PERF_LINK_WALL_TIME").is_none() {
+        return None;
+    }
+    let linker_fake = env::current_exe().ok()?.with_file_name("linker-fake");
+    if !linker_fake.is_file() {
+        eprintln!(
+            "RUSTC_PERF_LINK_WALL_TIME is set, but {} does not exist",
+            linker_fake.display()
+        );
+        return None;
+    }
+
+    let pid = std::process::id();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let file = env::temp_dir().join(format!("link-wall-time-{pid}-{timestamp}"));
+
+    let mut arg = OsString::from("-Clinker=");
+    arg.push(&linker_fake);
+    args.push(arg);
+    // Inherited by the linker-fake child processes.
+    env::set_var("LINK_WALL_TIME_FILE", &file);
+    Some(file)
+}
+
+/// Prints the total wall time recorded by `linker-fake`, summed over all
+/// linker invocations of this compilation.
+#[cfg(unix)]
+fn print_link_wall_time(file: &PathBuf) {
+    if let Ok(data) = fs::read_to_string(file) {
+        let total: f64 = data
+            .lines()
+            .filter_map(|line| line.parse::<f64>().ok())
+            .sum();
+        if total > 0.0 {
+            println!("!link-wall-time:{}", total);
+        }
+        let _ = fs::remove_file(file);
+    }
+}
+
+/// Direct perf-counter measurement through `perf_event_open`, used to avoid
+/// the fixed overhead of spawning a `perf stat` subprocess, which can dominate
+/// the measurement of very short compilations.
+///
+/// The counters are inherited by child processes and count user space only,
+/// matching the `instructions:u`/`cycles:u` events passed to `perf stat`.
+/// Opening them requires `/proc/sys/kernel/perf_event_paranoid` to be at most
+/// 2 (or `CAP_PERFMON`); when that fails the caller falls back to `perf stat`.
+#[cfg(target_os = "linux")]
+mod pmc {
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_TYPE_SOFTWARE: u32 = 1;
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_COUNT_SW_TASK_CLOCK: u64 = 1;
+
+    // Subset of the `perf_event_attr` flag bits, see `linux/perf_event.h`.
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    /// Layout-compatible with `struct perf_event_attr`.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup: u32,
+        bp_type: u32,
+        config1: u64,
+        config2: u64,
+        branch_sample_type: u64,
+        sample_regs_user: u64,
+        sample_stack_user: u32,
+        clockid: i32,
+        sample_regs_intr: u64,
+        aux_watermark: u32,
+        sample_max_stack: u16,
+        reserved: u16,
+    }
+
+    struct Counter {
+        fd: i32,
+    }
+
+    impl Counter {
+        fn open(type_: u32, config: u64) -> Option<Counter> {
+            let attr = PerfEventAttr {
+                type_,
+                size: std::mem::size_of::<PerfEventAttr>() as u32,
+                config,
+                // Count this process and all of its children, in user space
+                // only.
+                flags: FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+                ..PerfEventAttr::default()
+            };
+            let fd =
+                unsafe { libc::syscall(libc::SYS_perf_event_open, &attr, 0, -1, -1, 0) as i32 };
+            if fd < 0 {
+                None
+            } else {
+                Some(Counter { fd })
+            }
+        }
+
+        fn read(&self) -> u64 {
+            let mut value = 0u64;
+            let read = unsafe {
+                libc::read(
+                    self.fd,
+                    &mut value as *mut u64 as *mut libc::c_void,
+                    std::mem::size_of::<u64>(),
+                )
+            };
+            assert_eq!(read, 8, "failed to read perf counter");
+            value
+        }
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+
+    pub struct Counters {
+        instructions: Counter,
+        cycles: Counter,
+        task_clock: Counter,
+    }
+
+    impl Counters {
+        /// Opens the counters, which also serves as the capability probe:
+        /// returns `None` if the kernel refuses access.
+        pub fn start() -> Option<Counters> {
+            Some(Counters {
+                instructions: Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS)?,
+                cycles: Counter::open(PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES)?,
+                task_clock: Counter::open(PERF_TYPE_SOFTWARE, PERF_COUNT_SW_TASK_CLOCK)?,
+            })
+        }
+
+        /// Prints the counter values in the CSV format that `perf stat`
+        /// emits, so that `process_stat_output` does not need to distinguish
+        /// the two measurement mechanisms.
+        pub fn report(&self) {
+            println!("{};;instructions:u;4;100.00", self.instructions.read());
+            println!("{};;cycles:u;4;100.00", self.cycles.read());
+            // task-clock counts nanoseconds, `perf stat` reports milliseconds.
+            println!(
+                "{};msec;task-clock;4;100.00",
+                self.task_clock.read() as f64 / 1_000_000.0
+            );
+        }
+    }
+}
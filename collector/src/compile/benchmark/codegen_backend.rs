@@ -9,4 +9,22 @@ impl CodegenBackend {
     pub fn all() -> Vec<CodegenBackend> {
         vec![CodegenBackend::Llvm, CodegenBackend::Cranelift]
     }
+
+    /// Returns the `-Zcodegen-backend` flag that selects this backend for the
+    /// leaf rustc, if any.
+    ///
+    /// Normally the backend is referenced by name, which requires it to be
+    /// installed in the sysroot of the benchmarked toolchain. For locally
+    /// built backends, the `RUSTC_PERF_CODEGEN_BACKEND_PATH` environment
+    /// variable can point to the backend dylib directly.
+    pub fn rustc_flag(&self) -> Option<String> {
+        match self {
+            CodegenBackend::Llvm => None,
+            CodegenBackend::Cranelift => {
+                let backend = std::env::var("RUSTC_PERF_CODEGEN_BACKEND_PATH")
+                    .unwrap_or_else(|_| "cranelift".to_string());
+                Some(format!("-Zcodegen-backend={backend}"))
+            }
+        }
+    }
 }
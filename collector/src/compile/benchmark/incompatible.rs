@@ -0,0 +1,111 @@
+//! A persistent cache of benchmarks that are known not to build on a given
+//! compiler. During bisection across many commits, certain benchmarks
+//! reliably fail to build on older compilers, and retrying them on every
+//! commit wastes time; once a benchmark has been observed to fail to build,
+//! the cache skips it for sufficiently-similar compilers in subsequent runs.
+//!
+//! The cache lives in the JSON file named by the
+//! `RUSTC_PERF_INCOMPATIBLE_CACHE` environment variable. It maps each
+//! benchmark to the compiler versions it failed to build on, so it can be
+//! inspected with any JSON tool; deleting the file (or an entry in it)
+//! clears the cache.
+
+use crate::toolchain::Toolchain;
+use anyhow::Context;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::process::Command;
+
+pub const INCOMPATIBLE_CACHE_ENV_VAR: &str = "RUSTC_PERF_INCOMPATIBLE_CACHE";
+
+/// Returns the version-similarity key of the given toolchain: the
+/// `major.minor` part of the release version reported by `rustc --version`.
+///
+/// Two compilers are only considered similar when these match. This is
+/// deliberately conservative — a benchmark that failed to build on a 1.63
+/// nightly is skipped for other 1.63 compilers, but is retried as soon as the
+/// minor version changes, so a benchmark that would now build again is not
+/// wrongly skipped forever.
+pub fn version_key(toolchain: &Toolchain) -> Option<String> {
+    let output = Command::new(&toolchain.components.rustc)
+        .arg("--version")
+        .output()
+        .ok()?;
+    // `rustc 1.63.0-nightly (abcdef0123 2022-01-01)`
+    let version = String::from_utf8(output.stdout).ok()?;
+    let version = version.split_whitespace().nth(1)?;
+    let mut parts = version.split('.');
+    let major = parts.next()?;
+    let minor = parts.next()?;
+    Some(format!("{major}.{minor}"))
+}
+
+/// Returns true when the given benchmark failure looks like a failure to
+/// *build* the benchmark, as opposed to e.g. a measurement or I/O problem.
+/// Only build failures are cached, so that a transient failure does not cause
+/// a benchmark to be wrongly skipped on subsequent runs.
+pub fn is_build_failure(error: &anyhow::Error) -> bool {
+    let text = format!("{error:#}");
+    text.contains("error: could not compile") || text.contains("error[E")
+}
+
+#[derive(Default)]
+pub struct KnownIncompatibleCache {
+    path: PathBuf,
+    /// Maps each benchmark to the version keys it failed to build on.
+    /// Ordered maps keep the serialized file stable, so that diffs of the
+    /// cache between runs stay readable.
+    benchmarks: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl KnownIncompatibleCache {
+    /// Loads the cache from the file named by
+    /// `RUSTC_PERF_INCOMPATIBLE_CACHE`, or returns `None` when the variable
+    /// is not set. A missing file is an empty cache.
+    pub fn from_env() -> Option<anyhow::Result<Self>> {
+        let path = std::env::var_os(INCOMPATIBLE_CACHE_ENV_VAR).map(PathBuf::from)?;
+        Some(Self::load(path))
+    }
+
+    fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let benchmarks = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("cannot parse incompatible cache {:?}", path))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("cannot read incompatible cache {:?}", path))
+            }
+        };
+        Ok(Self { path, benchmarks })
+    }
+
+    pub fn is_incompatible(&self, benchmark: &str, version_key: &str) -> bool {
+        self.benchmarks
+            .get(benchmark)
+            .is_some_and(|versions| versions.contains(version_key))
+    }
+
+    /// Records that `benchmark` failed to build on a compiler with the given
+    /// version key and persists the cache. A failure to persist only loses
+    /// the time saving on the next run, so it is logged rather than
+    /// propagated.
+    pub fn record(&mut self, benchmark: &str, version_key: &str) {
+        let inserted = self
+            .benchmarks
+            .entry(benchmark.to_string())
+            .or_default()
+            .insert(version_key.to_string());
+        if inserted {
+            if let Err(error) = self.save() {
+                log::warn!("failed to save incompatible cache: {error:#}");
+            }
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let contents = serde_json::to_string_pretty(&self.benchmarks)?;
+        std::fs::write(&self.path, contents)
+            .with_context(|| format!("cannot write incompatible cache {:?}", self.path))
+    }
+}
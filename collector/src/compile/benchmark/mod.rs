@@ -3,26 +3,91 @@ use crate::compile::benchmark::codegen_backend::CodegenBackend;
 use crate::compile::benchmark::patch::Patch;
 use crate::compile::benchmark::profile::Profile;
 use crate::compile::benchmark::scenario::Scenario;
-use crate::compile::execute::{CargoProcess, Processor};
+use crate::compile::execute::bencher::InMemoryProcessor;
+use crate::compile::execute::{cross_target, CargoProcess, Processor, Stats};
 use crate::toolchain::Toolchain;
 use crate::utils::wait_for_future;
 use anyhow::{bail, Context};
 use log::debug;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::fs::File;
 use std::mem::ManuallyDrop;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use tempfile::TempDir;
 
 pub mod category;
 pub mod codegen_backend;
+pub mod incompatible;
 pub(crate) mod patch;
 pub mod profile;
+pub mod sanitizer;
 pub mod scenario;
 
-fn default_runs() -> usize {
-    3
+const DEFAULT_RUNS: usize = 3;
+
+fn default_runs() -> Runs {
+    Runs::Uniform(DEFAULT_RUNS)
+}
+
+/// Number of recorded iterations of each benchmark run: either a single count
+/// applied to every profile, or a map from profile name to count, for
+/// benchmarks where a cheap profile (e.g. `Check`) warrants more iterations
+/// than an expensive one (e.g. `Opt`). Profiles missing from the map use the
+/// default count.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Runs {
+    Uniform(usize),
+    PerProfile(HashMap<Profile, usize>),
+}
+
+impl Runs {
+    fn for_profile(&self, profile: Profile) -> usize {
+        match self {
+            Runs::Uniform(runs) => *runs,
+            Runs::PerProfile(map) => map.get(&profile).copied().unwrap_or(DEFAULT_RUNS),
+        }
+    }
+}
+
+/// The file(s) whose modification time is reset before an incremental build:
+/// either a single path (the common case) or a list of paths and globs, all
+/// resolved relative to the benchmark directory.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(untagged)]
+pub enum TouchFile {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl TouchFile {
+    fn patterns(&self) -> &[String] {
+        match self {
+            TouchFile::Single(pattern) => std::slice::from_ref(pattern),
+            TouchFile::Multiple(patterns) => patterns,
+        }
+    }
+}
+
+fn default_cumulative_patches() -> bool {
+    true
+}
+
+/// Returns the effective number of jobserver tokens that is used for building
+/// benchmarks: the number of cores, unless overridden through the
+/// `CARGO_THREAD_COUNT` environment variable.
+///
+/// This is recorded as collection metadata, since it explains preparation-time
+/// differences between machines with different core counts.
+pub fn jobserver_token_count() -> usize {
+    std::env::var("CARGO_THREAD_COUNT")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or_else(num_cpus::get)
 }
 
 #[derive(
@@ -46,22 +111,61 @@ impl Display for ArtifactType {
 
 /// This is the internal representation of an individual benchmark's
 /// perf-config.json file.
+///
+/// Unknown fields are rejected, so a typo'd field name fails loudly when the
+/// benchmark is registered instead of being silently ignored.
 #[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct BenchmarkConfig {
     cargo_opts: Option<String>,
     cargo_rustc_opts: Option<String>,
     cargo_toml: Option<String>,
     #[serde(default)]
     disabled: bool,
+
+    /// Environment variables to set for all cargo invocations of this
+    /// benchmark (preparation and measured runs alike), for crates whose
+    /// build needs them (e.g. `PROTOC` or `OPENSSL_DIR`). These cannot
+    /// override the variables the collector itself controls (such as
+    /// `CARGO_INCREMENTAL` and `RUSTC_BOOTSTRAP`), which are applied last.
+    #[serde(default)]
+    env: HashMap<String, String>,
+
+    /// Name of a group of benchmarks that are variants of the same crate
+    /// (e.g. the same crate built with different flags). Benchmarks in the same
+    /// group share the expensive preparation of their dependencies: the first
+    /// variant prepares them and later variants clone the prepared state
+    /// instead of building the dependencies from scratch.
+    #[serde(default)]
+    group: Option<String>,
     #[serde(default = "default_runs")]
-    runs: usize,
+    runs: Runs,
+
+    /// Flags passed to every rustc invocation via the `RUSTFLAGS` environment
+    /// variable, so they apply to the whole dependency graph (e.g.
+    /// `-C target-cpu=native`). Unlike `cargo_rustc_opts`, which goes after
+    /// `cargo rustc --` and therefore only reaches the leaf crate. Cargo
+    /// forwards the flags to the `rustc-fake` shim like any other arguments,
+    /// so they reach the real rustc unchanged.
+    #[serde(default)]
+    rustflags: Option<String>,
+
+    /// Hard wall-clock limit in seconds for every cargo invocation of this
+    /// benchmark. When exceeded, the cargo process and all of its children
+    /// (cargo spawns rustc) are killed and the benchmark is recorded as
+    /// failed, instead of hanging the whole collection on e.g. a runaway
+    /// proc macro. No limit by default.
+    #[serde(default)]
+    timeout: Option<u64>,
 
     /// The file that should be touched to ensure cargo re-checks the leaf crate
-    /// we're interested in. Likely, something similar to `src/lib.rs`. The
-    /// default if this is not present is to touch all .rs files in the
-    /// directory that `Cargo.toml` is in.
+    /// we're interested in. Likely, something similar to `src/lib.rs`. Can
+    /// also be a list of paths and simple globs (e.g. `src/**/*.rs`) for
+    /// crates where several leaf files have to change to trigger the right
+    /// rebuild. The default if this is not present is to touch all .rs files
+    /// in the directory that `Cargo.toml` is in.
     #[serde(default)]
-    touch_file: Option<String>,
+    touch_file: Option<TouchFile>,
 
     category: Category,
 
@@ -70,14 +174,84 @@ pub struct BenchmarkConfig {
     #[serde(default)]
     excluded_profiles: HashSet<Profile>,
 
+    /// Whether the `IncrPatched` patches are applied cumulatively, each on
+    /// top of the previous one (modelling a sequence of edits: edit 1, then
+    /// edit 2 on top of edit 1). This is the default. When disabled, each
+    /// patch is reverted after its measured build, so every patch applies to
+    /// the base sources. Only the source tree is reset between patches; the
+    /// incremental cache always carries over from the previous build.
+    #[serde(default = "default_cumulative_patches")]
+    cumulative_patches: bool,
+
     /// Scenarios that are not useful for this benchmark.
     /// They will be ignored during benchmarking.
     #[serde(default)]
     excluded_scenarios: HashSet<Scenario>,
 
+    /// Crate types that should be measured for this benchmark (e.g. `rlib`,
+    /// `dylib`, `cdylib`). When non-empty, the benchmark is expanded into one
+    /// variant per crate type, whose results (including artifact sizes) are
+    /// recorded under a crate-type-tagged name, so that the crate types can be
+    /// compared side by side.
+    #[serde(default)]
+    crate_types: Vec<String>,
+
+    /// Paths (relative to the benchmark directory) of alternate `Cargo.lock`
+    /// files, used to study the compile-time impact of dependency versions.
+    /// When non-empty, the benchmark is expanded into one variant per
+    /// lockfile, which is copied over the committed `Cargo.lock` before the
+    /// build and measured under a lockfile-tagged name. The builds run with
+    /// `--locked`, so that cargo honors the provided lockfile exactly.
+    #[serde(default)]
+    lockfiles: Vec<String>,
+
+    /// The alternate lockfile of this benchmark variant, filled in when
+    /// expanding `lockfiles`.
+    #[serde(skip)]
+    lockfile: Option<String>,
+
+    /// Names of workspace members to measure individually. When non-empty,
+    /// the benchmark is expanded into one variant per member, each compiled
+    /// with `-p <member>` and recorded under a member-tagged name. This gives
+    /// crate-level granularity for multi-crate workspace benchmarks, instead
+    /// of attributing all the work to a single leaf crate. The list is
+    /// explicit to keep the number of measured configurations predictable.
+    #[serde(default)]
+    workspace_members: Vec<String>,
+
+    /// The workspace member measured by this benchmark variant, filled in
+    /// when expanding `workspace_members`.
+    #[serde(skip)]
+    package: Option<String>,
+
     artifact: ArtifactType,
 }
 
+/// Estimated ratio between the size of a benchmark's sources and the peak size
+/// of one of its build directories (sources, dependencies and target
+/// artifacts), used for the pre-flight disk-space check. Target sizes vary
+/// widely across benchmarks, so the factor can be overridden with the
+/// `RUSTC_PERF_DISK_SPACE_FACTOR` environment variable; `0` disables the
+/// check.
+#[cfg(unix)]
+const DISK_SPACE_FACTOR: u64 = 30;
+
+/// Environment variable pointing to a persistent directory used as a shared
+/// dependency-artifact cache across benchmarks and collector invocations.
+const SHARED_TARGET_CACHE_ENV_VAR: &str = "RUSTC_PERF_SHARED_TARGET_CACHE";
+
+/// The crate types that rustc understands, used to validate the `crate_types`
+/// field of perf-config.json.
+const KNOWN_CRATE_TYPES: &[&str] = &[
+    "bin",
+    "lib",
+    "rlib",
+    "dylib",
+    "cdylib",
+    "staticlib",
+    "proc-macro",
+];
+
 impl BenchmarkConfig {
     pub fn category(&self) -> Category {
         self.category
@@ -87,9 +261,109 @@ impl BenchmarkConfig {
         self.artifact
     }
 
-    pub fn iterations(&self) -> usize {
-        self.runs
+    pub fn iterations(&self, profile: Profile) -> usize {
+        self.runs.for_profile(profile)
+    }
+}
+
+/// Key identifying shared preparation state of a benchmark group.
+/// The cargo arguments are part of the key, because variant-specific flags
+/// (e.g. `--features`) can change the dependency graph, in which case the
+/// prepared dependencies cannot be shared.
+#[derive(Clone, Eq, Hash, PartialEq)]
+struct GroupPrepKey {
+    group: String,
+    backend: CodegenBackend,
+    profile: Profile,
+    cargo_opts: Option<String>,
+    lockfile: Option<String>,
+}
+
+/// Caches prepared directories (benchmark sources plus a target directory with
+/// built dependencies) of benchmarks that declare the same `group` in their
+/// perf-config.json file. The cache should be kept alive for the whole
+/// collection, so that benchmarks in the same group can share it.
+#[derive(Default)]
+pub struct GroupPreparationCache {
+    dirs: RefCell<HashMap<GroupPrepKey, TempDir>>,
+}
+
+impl GroupPreparationCache {
+    fn lookup(&self, key: &GroupPrepKey) -> Option<PathBuf> {
+        self.dirs
+            .borrow()
+            .get(key)
+            .map(|dir| dir.path().to_path_buf())
+    }
+
+    fn store(
+        &self,
+        key: GroupPrepKey,
+        prepare: impl FnOnce() -> anyhow::Result<TempDir>,
+    ) -> anyhow::Result<()> {
+        if !self.dirs.borrow().contains_key(&key) {
+            let dir = prepare()?;
+            self.dirs.borrow_mut().insert(key, dir);
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for an adaptive run count: instead of running a fixed number of
+/// iterations, keep iterating until the coefficient of variation of the
+/// primary stat (`instructions:u`, falling back to `wall-time`) across the
+/// finished iterations drops below `cv_threshold`, bounded by `max_runs`.
+/// This avoids wasting time on stable benchmarks while giving noisy ones
+/// enough iterations to produce a usable estimate.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveRunCount {
+    pub cv_threshold: f64,
+    pub max_runs: usize,
+}
+
+/// A shared flag through which a controlling process can request a clean stop
+/// of an ongoing collection. `Benchmark::measure` checks it between runs, so
+/// in-flight cargo invocations and database writes complete normally before
+/// it returns [`MeasureOutcome::Cancelled`].
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// How a `Benchmark::measure` call ended: either all requested iterations
+/// ran, or a cooperative cancellation stopped it early between runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasureOutcome {
+    Completed,
+    Cancelled,
+}
+
+/// The sample coefficient of variation (standard deviation divided by mean)
+/// of the given values. Returns `None` for fewer than two samples or a
+/// non-positive mean, in which case no stability judgement can be made.
+fn coefficient_of_variation(samples: &[f64]) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
     }
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    if mean <= 0.0 {
+        return None;
+    }
+    let variance = samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Some(variance.sqrt() / mean)
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
@@ -106,6 +380,9 @@ pub struct Benchmark {
     pub path: PathBuf,
     pub patches: Vec<Patch>,
     config: BenchmarkConfig,
+    /// Cached `-p` spec of the benchmark package, resolved lazily by the
+    /// first cargo invocation (see `CargoProcess::get_pkgid`).
+    pkgid_cache: OnceLock<String>,
 }
 
 impl Benchmark {
@@ -135,18 +412,351 @@ impl Benchmark {
             bail!("missing a perf-config.json file for `{}`", name);
         };
 
+        for crate_type in &config.crate_types {
+            if !KNOWN_CRATE_TYPES.contains(&crate_type.as_str()) {
+                bail!(
+                    "unknown crate type `{}` for `{}`: expected one of {:?}",
+                    crate_type,
+                    name,
+                    KNOWN_CRATE_TYPES
+                );
+            }
+        }
+
+        for lockfile in &config.lockfiles {
+            if !path.join(lockfile).is_file() {
+                bail!("lockfile `{}` for `{}` does not exist", lockfile, name);
+            }
+        }
+
+        if let Some(cargo_toml) = &config.cargo_toml {
+            if !path.join(cargo_toml).is_file() {
+                bail!("cargo_toml `{}` for `{}` does not exist", cargo_toml, name);
+            }
+        }
+
+        if let Some(touch_file) = &config.touch_file {
+            // Globs are only resolved against the build directory at run
+            // time, so only literal paths can be checked here.
+            for pattern in touch_file.patterns() {
+                if !pattern.contains(['*', '?']) && !path.join(pattern).is_file() {
+                    bail!("touch_file `{}` for `{}` does not exist", pattern, name);
+                }
+            }
+        }
+
+        match &config.runs {
+            Runs::Uniform(0) => bail!("`runs` for `{}` must be greater than zero", name),
+            Runs::PerProfile(map) => {
+                if let Some((profile, _)) = map.iter().find(|(_, runs)| **runs == 0) {
+                    bail!(
+                        "`runs` for `{}` must be greater than zero (profile {:?})",
+                        name,
+                        profile
+                    );
+                }
+            }
+            Runs::Uniform(_) => {}
+        }
+
+        // A standardized, benchmark-agnostic incremental scenario: append one
+        // trivial function to the benchmark's leaf file, exercising
+        // incremental codegen of a single new item with maximal cache reuse
+        // everywhere else.
+        if std::env::var_os("RUSTC_PERF_APPEND_FN_PATCH").is_some() {
+            let file = config
+                .touch_file
+                .as_ref()
+                .and_then(|touch_file| {
+                    touch_file
+                        .patterns()
+                        .iter()
+                        .find(|pattern| !pattern.contains(['*', '?']))
+                        .map(PathBuf::from)
+                })
+                .or_else(|| {
+                    ["src/lib.rs", "src/main.rs"]
+                        .iter()
+                        .map(PathBuf::from)
+                        .find(|candidate| path.join(candidate).is_file())
+                });
+            match file {
+                Some(file) => patches.push(Patch::new_append_function(patches.len(), file)),
+                None => eprintln!(
+                    "Skipping synthetic append patch for {}: cannot determine a file to append to",
+                    name
+                ),
+            }
+        }
+
         Ok(Benchmark {
             name: BenchmarkName(name),
             path,
             patches,
             config,
+            pkgid_cache: OnceLock::new(),
         })
     }
 
+    /// Expands a benchmark that declares `crate_types` in its perf-config.json
+    /// into one variant per crate type, each measured under a crate-type-tagged
+    /// name. A variant that cannot be built as its crate type (e.g. a `cdylib`
+    /// without exported symbols) fails on its own without affecting the other
+    /// variants.
+    fn into_crate_type_variants(self) -> Vec<Benchmark> {
+        if self.config.crate_types.is_empty() {
+            return vec![self];
+        }
+        self.config
+            .crate_types
+            .iter()
+            .map(|crate_type| {
+                let mut config = self.config.clone();
+                // `--crate-type` is reserved by cargo on the rustc command
+                // line, so it has to be passed as an argument of the
+                // `cargo rustc` subcommand instead.
+                config.cargo_opts = Some(match &self.config.cargo_opts {
+                    Some(opts) => format!("{opts} --crate-type {crate_type}"),
+                    None => format!("--crate-type {crate_type}"),
+                });
+                // The `cargo rustdoc` and `cargo clippy` subcommands do not
+                // accept `--crate-type`.
+                config.excluded_profiles.insert(Profile::Doc);
+                config.excluded_profiles.insert(Profile::Clippy);
+                Benchmark {
+                    name: BenchmarkName(format!("{}-{}", self.name, crate_type)),
+                    path: self.path.clone(),
+                    patches: self.patches.clone(),
+                    config,
+                    pkgid_cache: OnceLock::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Expands this benchmark into one variant per requested sanitizer, each
+    /// building the leaf crate with the corresponding `-Zsanitizer=` flag and
+    /// measured under a sanitizer-tagged name (`<name>-<tag>`). With an empty
+    /// sanitizer list the benchmark is returned unchanged. A benchmark that
+    /// cannot build with one sanitizer fails only that variant, not the whole
+    /// suite.
+    pub fn into_sanitizer_variants(
+        self,
+        sanitizers: &[crate::compile::benchmark::sanitizer::Sanitizer],
+    ) -> Vec<Benchmark> {
+        if sanitizers.is_empty() {
+            return vec![self];
+        }
+        sanitizers
+            .iter()
+            .map(|sanitizer| {
+                let mut config = self.config.clone();
+                let flag = sanitizer.rustc_flag();
+                config.cargo_rustc_opts = Some(match &self.config.cargo_rustc_opts {
+                    Some(opts) => format!("{opts} {flag}"),
+                    None => flag.to_string(),
+                });
+                Benchmark {
+                    name: BenchmarkName(format!("{}-{}", self.name, sanitizer.tag())),
+                    path: self.path.clone(),
+                    patches: self.patches.clone(),
+                    config,
+                    pkgid_cache: OnceLock::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Expands a benchmark that declares `workspace_members` in its
+    /// perf-config.json into one variant per member, each measured under a
+    /// member-tagged name. Cargo only passes the wrapped-rustc arguments to
+    /// the crate selected with `-p`, so each variant wraps and attributes
+    /// exactly one member's rustc invocation.
+    fn into_workspace_member_variants(self) -> Vec<Benchmark> {
+        if self.config.workspace_members.is_empty() {
+            return vec![self];
+        }
+        self.config
+            .workspace_members
+            .iter()
+            .map(|member| {
+                let mut config = self.config.clone();
+                config.package = Some(member.clone());
+                Benchmark {
+                    name: BenchmarkName(format!("{}-{}", self.name, member)),
+                    path: self.path.clone(),
+                    patches: self.patches.clone(),
+                    config,
+                    pkgid_cache: OnceLock::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Expands this benchmark into one variant per requested `-Cdebuginfo`
+    /// level, each building the leaf crate with the corresponding flag and
+    /// measured under a debuginfo-tagged name (`<name>-debuginfo-<level>`).
+    /// With an empty level list the benchmark is returned unchanged. Cargo's
+    /// default debuginfo level varies by profile, so sweeping the levels
+    /// explicitly makes the compile-time and artifact-size cost of debuginfo
+    /// visible (pair with `RUSTC_PERF_SECTION_SIZES` for the size side).
+    pub fn into_debuginfo_variants(self, levels: &[String]) -> Vec<Benchmark> {
+        if levels.is_empty() {
+            return vec![self];
+        }
+        levels
+            .iter()
+            .map(|level| {
+                let mut config = self.config.clone();
+                let flag = format!("-Cdebuginfo={level}");
+                config.cargo_rustc_opts = Some(match &self.config.cargo_rustc_opts {
+                    Some(opts) => format!("{opts} {flag}"),
+                    None => flag,
+                });
+                Benchmark {
+                    name: BenchmarkName(format!("{}-debuginfo-{}", self.name, level)),
+                    path: self.path.clone(),
+                    patches: self.patches.clone(),
+                    config,
+                    pkgid_cache: OnceLock::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Expands a benchmark that declares `lockfiles` in its perf-config.json
+    /// into one variant per alternate lockfile, each measured under a
+    /// lockfile-tagged name. The committed `Cargo.lock` stays in use for
+    /// benchmarks without this field.
+    fn into_lockfile_variants(self) -> Vec<Benchmark> {
+        if self.config.lockfiles.is_empty() {
+            return vec![self];
+        }
+        self.config
+            .lockfiles
+            .iter()
+            .map(|lockfile| {
+                let tag = Path::new(lockfile)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or(lockfile.as_str());
+                let mut config = self.config.clone();
+                config.lockfile = Some(lockfile.clone());
+                // Make cargo fail instead of silently regenerating the
+                // lockfile when it does not match the manifests.
+                config.cargo_opts = Some(match &self.config.cargo_opts {
+                    Some(opts) => format!("{opts} --locked"),
+                    None => "--locked".to_string(),
+                });
+                Benchmark {
+                    name: BenchmarkName(format!("{}-{}", self.name, tag)),
+                    path: self.path.clone(),
+                    patches: self.patches.clone(),
+                    config,
+                    pkgid_cache: OnceLock::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Times a plain `cargo metadata` invocation for this benchmark, which
+    /// measures the cost of dependency resolution separately from
+    /// compilation. For very large workspaces this can take noticeable time
+    /// that is invisible in the compile-time statistics.
+    pub fn time_cargo_metadata(&self, toolchain: &Toolchain) -> anyhow::Result<std::time::Duration> {
+        let mut cmd = std::process::Command::new(&toolchain.components.cargo);
+        cmd.current_dir(&self.path)
+            .arg("metadata")
+            .arg("--format-version")
+            .arg("1")
+            .arg("--manifest-path")
+            .arg(
+                self.config
+                    .cargo_toml
+                    .clone()
+                    .unwrap_or_else(|| String::from("Cargo.toml")),
+            )
+            .env("RUSTC", &toolchain.components.rustc);
+        let start = std::time::Instant::now();
+        crate::command_output(&mut cmd)
+            .with_context(|| format!("failed to run `cargo metadata` for {}", self.name))?;
+        Ok(start.elapsed())
+    }
+
     pub fn category(&self) -> Category {
         self.config.category
     }
 
+    /// Returns the shared-target-cache entry for this configuration, when
+    /// `RUSTC_PERF_SHARED_TARGET_CACHE` points to a persistent cache directory
+    /// and the benchmark has a lockfile to key the entry by.
+    ///
+    /// The key covers everything that can change what cargo builds for the
+    /// dependencies: the toolchain, the codegen backend, the profile, the
+    /// variant-specific cargo arguments and the full lockfile contents. Cargo
+    /// additionally revalidates every reused artifact against its own
+    /// fingerprints, so a stale entry only ever costs a rebuild, never an
+    /// invalid measurement.
+    fn shared_target_cache_entry(
+        &self,
+        toolchain: &Toolchain,
+        backend: CodegenBackend,
+        profile: Profile,
+    ) -> Option<PathBuf> {
+        let root = std::env::var_os(SHARED_TARGET_CACHE_ENV_VAR).map(PathBuf::from)?;
+        let lockfile_path = match &self.config.lockfile {
+            Some(lockfile) => self.path.join(lockfile),
+            None => self.path.join("Cargo.lock"),
+        };
+        // Without a lockfile the dependency set is not pinned, so there is
+        // nothing stable to key the cache by.
+        let lockfile = std::fs::read(&lockfile_path).ok()?;
+
+        let mut key = format!(
+            "{}\0{}\0{:?}\0{:?}\0{:?}\0{:?}\0",
+            toolchain.id,
+            toolchain.triple,
+            backend,
+            profile,
+            self.config.cargo_opts,
+            self.config.cargo_rustc_opts,
+        )
+        .into_bytes();
+        key.extend_from_slice(&lockfile);
+        Some(root.join(crate::utils::checksum::sha256_hex(&key)))
+    }
+
+    /// Copies the prepared dependency artifacts of `prep_dir` into the shared
+    /// target cache, unless an entry for this configuration already exists.
+    /// The copy is staged under a temporary name and renamed into place, so a
+    /// concurrently running collector never observes a half-written entry.
+    fn store_shared_target_cache_entry(&self, entry: &Path, prep_dir: &Path) -> anyhow::Result<()> {
+        let target = prep_dir.join("target");
+        if entry.is_dir() || !target.is_dir() {
+            return Ok(());
+        }
+        if let Some(parent) = entry.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let staging = entry.with_extension(format!("tmp-{}", std::process::id()));
+        std::fs::create_dir_all(&staging)?;
+        let mut target_dot = target;
+        target_dot.push(".");
+        Self::copy(&target_dot, &staging)?;
+        crate::utils::fs::rename(&staging, entry)?;
+        Ok(())
+    }
+
+    fn group_prep_key(&self, backend: CodegenBackend, profile: Profile) -> Option<GroupPrepKey> {
+        self.config.group.as_ref().map(|group| GroupPrepKey {
+            group: group.clone(),
+            backend,
+            profile,
+            cargo_opts: self.config.cargo_opts.clone(),
+            lockfile: self.config.lockfile.clone(),
+        })
+    }
+
     #[cfg(windows)]
     fn copy(from: &Path, to: &Path) -> anyhow::Result<()> {
         crate::utils::fs::robocopy(from, to, &[])
@@ -157,12 +767,83 @@ impl Benchmark {
         use crate::command_output;
         use std::process::Command;
 
+        // Copy-on-write copies are near-instant on filesystems that support
+        // them (btrfs, XFS, APFS), while the deep copy below can dominate the
+        // non-measured time of a benchmark run with many iterations. GNU cp
+        // spells the fast path `--reflink`, the macOS cp spells it `-c`; both
+        // fail when the flag or the filesystem does not support it, in which
+        // case we fall back to the deep copy and stop trying.
+        static REFLINK_SUPPORTED: std::sync::atomic::AtomicBool =
+            std::sync::atomic::AtomicBool::new(true);
+        if REFLINK_SUPPORTED.load(std::sync::atomic::Ordering::Relaxed) {
+            let reflink_flag = if cfg!(target_os = "macos") {
+                "-c"
+            } else {
+                "--reflink"
+            };
+            let mut cmd = Command::new("cp");
+            cmd.arg(reflink_flag).arg("-pLR").arg(from).arg(to);
+            match command_output(&mut cmd) {
+                Ok(_) => return Ok(()),
+                Err(error) => {
+                    log::debug!(
+                        "reflink copy failed, falling back to a deep copy: {:?}",
+                        error
+                    );
+                    REFLINK_SUPPORTED.store(false, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+        }
+
         let mut cmd = Command::new("cp");
         cmd.arg("-pLR").arg(from).arg(to);
         command_output(&mut cmd)?;
         Ok(())
     }
 
+    /// Checks that the filesystems holding the temporary build directories and
+    /// the benchmark sources have enough free space for this benchmark before
+    /// any build starts.
+    ///
+    /// The estimate is the size of the benchmark sources multiplied by the
+    /// number of profiles and by [`DISK_SPACE_FACTOR`], which accounts for the
+    /// target artifacts produced by the builds.
+    #[cfg(unix)]
+    fn check_disk_space(&self, profile_count: usize) -> anyhow::Result<()> {
+        let factor = std::env::var("RUSTC_PERF_DISK_SPACE_FACTOR")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(DISK_SPACE_FACTOR);
+        if factor == 0 {
+            return Ok(());
+        }
+        let (_, source_size) = crate::utils::fs::get_file_count_and_size(&self.path)
+            .with_context(|| format!("cannot compute size of {}", self.path.display()))?;
+        let required = source_size * profile_count.max(1) as u64 * factor;
+
+        let temp_dir = std::env::temp_dir();
+        for dir in [temp_dir.as_path(), self.path.as_path()] {
+            let free = crate::utils::fs::get_free_disk_space(dir)
+                .with_context(|| format!("cannot determine free space of {}", dir.display()))?;
+            if free < required {
+                bail!(
+                    "insufficient disk space to benchmark {}: an estimated {} is needed on `{}`, \
+                     but only {} is free ({} of sources × {} profile(s) × factor {}; the factor \
+                     can be overridden with the RUSTC_PERF_DISK_SPACE_FACTOR environment \
+                     variable, 0 disables this check)",
+                    self.name,
+                    humansize::format_size(required, humansize::BINARY),
+                    dir.display(),
+                    humansize::format_size(free, humansize::BINARY),
+                    humansize::format_size(source_size, humansize::BINARY),
+                    profile_count.max(1),
+                    factor
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn make_temp_dir(&self, base: &Path) -> anyhow::Result<TempDir> {
         // Appending `.` means we copy just the contents of `base` into
         // `tmp_dir`, rather than `base` itself.
@@ -218,12 +899,62 @@ impl Benchmark {
                 .split_whitespace()
                 .map(String::from)
                 .collect(),
-            touch_file: self.config.touch_file.clone(),
+            rustflags: self.config.rustflags.clone(),
+            touch_file: self
+                .config
+                .touch_file
+                .as_ref()
+                .map(|touch_file| touch_file.patterns().to_vec()),
+            env: self.config.env.clone(),
+            timeout: self.config.timeout.map(std::time::Duration::from_secs),
+            dry_run: std::env::var_os("RUSTC_PERF_DRY_RUN").is_some(),
+            target: cross_target(),
             jobserver: None,
+            package: self.config.package.clone(),
+            pkgid_cache: &self.pkgid_cache,
         }
     }
 
+    /// Returns the `IncrPatched` patches in the order they should be applied.
+    ///
+    /// The default is the fixed numeric order of the patch files. The
+    /// `RUSTC_PERF_PATCH_ORDER` environment variable selects an alternate
+    /// order — either `reversed`, or a comma-separated list of patch names —
+    /// to detect incremental costs that are sensitive to the cache state left
+    /// behind by earlier edits. An explicit list also selects which patches
+    /// run, so a single suspicious sequence can be replayed in isolation.
+    fn patch_order(&self) -> anyhow::Result<Vec<Patch>> {
+        let Ok(order) = std::env::var("RUSTC_PERF_PATCH_ORDER") else {
+            return Ok(self.patches.clone());
+        };
+        if order == "reversed" {
+            return Ok(self.patches.iter().rev().cloned().collect());
+        }
+        order
+            .split(',')
+            .map(|name| {
+                self.patches
+                    .iter()
+                    .find(|patch| patch.name.as_str() == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "RUSTC_PERF_PATCH_ORDER names unknown patch `{}` for {}; \
+                             available patches: {:?}",
+                            name,
+                            self.name,
+                            self.patches
+                                .iter()
+                                .map(|patch| patch.name.as_str())
+                                .collect::<Vec<_>>()
+                        )
+                    })
+            })
+            .collect()
+    }
+
     /// Run a specific benchmark under a processor + profiler combination.
+    #[allow(clippy::too_many_arguments)]
     pub async fn measure(
         &self,
         processor: &mut dyn Processor,
@@ -232,14 +963,15 @@ impl Benchmark {
         backends: &[CodegenBackend],
         toolchain: &Toolchain,
         iterations: Option<usize>,
-    ) -> anyhow::Result<()> {
+        adaptive: Option<AdaptiveRunCount>,
+        group_cache: Option<&GroupPreparationCache>,
+        cancel: Option<&CancellationToken>,
+    ) -> anyhow::Result<MeasureOutcome> {
         if self.config.disabled {
             eprintln!("Skipping {}: disabled", self.name);
             bail!("disabled benchmark");
         }
 
-        let iterations = iterations.unwrap_or(self.config.runs);
-
         let profiles: Vec<Profile> = profiles
             .iter()
             .copied()
@@ -248,7 +980,17 @@ impl Benchmark {
 
         if profiles.is_empty() {
             eprintln!("Skipping {}: no profiles selected", self.name);
-            return Ok(());
+            return Ok(MeasureOutcome::Completed);
+        }
+
+        for scenario in scenarios
+            .iter()
+            .filter(|scenario| self.config.excluded_scenarios.contains(scenario))
+        {
+            eprintln!(
+                "Skipping {}: {:?} scenario disabled in perf-config.json",
+                self.name, scenario
+            );
         }
 
         let scenarios: Vec<Scenario> = scenarios
@@ -257,16 +999,87 @@ impl Benchmark {
             .filter(|scenario| !self.config.excluded_scenarios.contains(scenario))
             .collect();
 
+        // Running out of disk space deep inside a build fails in confusing
+        // ways and leaves partial state behind, so fail fast instead.
+        #[cfg(unix)]
+        self.check_disk_space(profiles.len())?;
+
         if scenarios.is_empty() {
             eprintln!("Skipping {}: no scenarios selected", self.name);
-            return Ok(());
+            return Ok(MeasureOutcome::Completed);
+        }
+
+        // The IncrFull build is a prerequisite of the other incremental
+        // scenarios, so it cannot be disabled while a dependent incremental
+        // scenario remains enabled.
+        if self.config.excluded_scenarios.contains(&Scenario::IncrFull)
+            && scenarios.iter().any(|s| s.is_incr())
+        {
+            bail!(
+                "IncrFull is disabled for `{}`, but the dependent incremental scenario(s) {:?} \
+                 are not: either enable IncrFull or disable them as well",
+                self.name,
+                scenarios.iter().filter(|s| s.is_incr()).collect::<Vec<_>>()
+            );
         }
 
         eprintln!("Preparing {}", self.name);
         let mut target_dirs: Vec<((CodegenBackend, Profile), TempDir)> = vec![];
         for backend in backends {
             for profile in &profiles {
-                target_dirs.push(((*backend, *profile), self.make_temp_dir(&self.path)?));
+                // If another benchmark from the same group has already prepared
+                // its dependencies, clone the shared prepared state instead of
+                // starting from the pristine benchmark sources.
+                let shared_prep = group_cache
+                    .zip(self.group_prep_key(*backend, *profile))
+                    .and_then(|(cache, key)| cache.lookup(&key));
+                let prep_dir = match shared_prep {
+                    Some(shared) => {
+                        log::debug!(
+                            "{}: reusing shared group preparation for {:?} + {:?}",
+                            self.name,
+                            backend,
+                            profile
+                        );
+                        self.make_temp_dir(&shared)?
+                    }
+                    None => self.make_temp_dir(&self.path)?,
+                };
+                // Swap in the alternate lockfile of this variant before any
+                // build happens, so that the dependencies are prepared
+                // according to it.
+                if let Some(lockfile) = &self.config.lockfile {
+                    std::fs::copy(
+                        self.path.join(lockfile),
+                        prep_dir.path().join("Cargo.lock"),
+                    )
+                    .with_context(|| {
+                        format!("cannot copy lockfile `{}` for {}", lockfile, self.name)
+                    })?;
+                }
+                // Seed the target directory with prebuilt dependency
+                // artifacts from the shared target cache, so that preparation
+                // only has to build what this benchmark does not share with
+                // earlier ones. The leaf crate is rebuilt during measurement
+                // regardless, so only unmeasured work is skipped.
+                let shared_target = self
+                    .shared_target_cache_entry(toolchain, *backend, *profile)
+                    .filter(|entry| entry.is_dir());
+                if let Some(entry) = shared_target {
+                    log::debug!(
+                        "{}: seeding target dir for {:?} + {:?} from the shared target cache",
+                        self.name,
+                        backend,
+                        profile
+                    );
+                    let target = prep_dir.path().join("target");
+                    std::fs::create_dir_all(&target)?;
+                    let mut entry_dot = entry;
+                    entry_dot.push(".");
+                    Self::copy(&entry_dot, &target)
+                        .with_context(|| format!("copying shared target cache for {}", self.name))?;
+                }
+                target_dirs.push(((*backend, *profile), prep_dir));
             }
         }
 
@@ -297,7 +1110,11 @@ impl Benchmark {
         // different codegen backends are stored in separate directories.
         let preparation_start = std::time::Instant::now();
         std::thread::scope::<_, anyhow::Result<()>>(|s| {
-            let server = jobserver::Client::new(num_cpus::get()).context("jobserver::new")?;
+            // Respect the same `CARGO_THREAD_COUNT` cap as the measured
+            // builds, so the preparation phase does not contend with other
+            // jobs on a shared machine.
+            let server =
+                jobserver::Client::new(jobserver_token_count()).context("jobserver::new")?;
             let mut threads = Vec::with_capacity(target_dirs.len());
             for ((backend, profile), prep_dir) in &target_dirs {
                 let server = server.clone();
@@ -332,15 +1149,64 @@ impl Benchmark {
             preparation_start.elapsed().as_secs_f64()
         );
 
+        // Store the prepared state for later benchmarks from the same group.
+        // We store a copy, so that the measured runs below cannot modify it.
+        if let Some(cache) = group_cache {
+            for ((backend, profile), prep_dir) in &target_dirs {
+                if let Some(key) = self.group_prep_key(*backend, *profile) {
+                    cache.store(key, || self.make_temp_dir(prep_dir.path()))?;
+                }
+            }
+        }
+
+        // Populate the shared target cache with the freshly prepared
+        // dependency artifacts. A cache failure only loses the time saving,
+        // so it is logged rather than failing the benchmark.
+        for ((backend, profile), prep_dir) in &target_dirs {
+            if let Some(entry) = self.shared_target_cache_entry(toolchain, *backend, *profile) {
+                if let Err(error) = self.store_shared_target_cache_entry(&entry, prep_dir.path()) {
+                    log::warn!(
+                        "{}: failed to store shared target cache entry: {:#}",
+                        self.name,
+                        error
+                    );
+                }
+            }
+        }
+
+        // The order in which the IncrPatched patches are applied (the fixed
+        // numeric order unless overridden via `RUSTC_PERF_PATCH_ORDER`).
+        let patches = self.patch_order()?;
+
+        // Without patches the patched-scenario loops below run zero times,
+        // which would look like a success while recording no data — usually a
+        // sign of forgotten `.patch` files in a new benchmark.
+        if patches.is_empty()
+            && (scenarios.contains(&Scenario::IncrPatched)
+                || scenarios.contains(&Scenario::IncrReverted))
+        {
+            log::warn!(
+                "benchmark `{}` has no patches; the IncrPatched scenario will record no data",
+                self.name
+            );
+        }
+
         // We need to hold on to the directories to keep the files alive until
         // the processor post-processes them. We also store them in `ManuallyDrop`
         // so that they are not deleted when an error occurs.
         let mut timing_dirs: Vec<ManuallyDrop<TempDir>> = vec![];
 
         let benchmark_start = std::time::Instant::now();
+        let mut cancelled = false;
         for ((backend, profile), prep_dir) in &target_dirs {
+            if cancelled {
+                break;
+            }
             let backend = *backend;
             let profile = *profile;
+            // A CLI-provided iteration count overrides the per-profile (or
+            // uniform) `runs` value from perf-config.json.
+            let iterations = iterations.unwrap_or_else(|| self.config.runs.for_profile(profile));
             eprintln!(
                 "Running {}: {:?} + {:?} + {:?}",
                 self.name, profile, scenarios, backend
@@ -349,26 +1215,59 @@ impl Benchmark {
             // We want at least two runs for all benchmarks (since we run
             // self-profile separately).
             processor.start_first_collection();
-            for i in 0..std::cmp::max(iterations, 2) {
-                if i == 1 {
+            let max_iterations = match adaptive {
+                Some(adaptive) => std::cmp::max(adaptive.max_runs, 2),
+                None => std::cmp::max(iterations, 2),
+            };
+            // One extra initial iteration whose results are thrown away
+            // (`RUSTC_PERF_WARMUP`), so that disk and page caches are warm
+            // before the first recorded run. This is independent of the
+            // self-profile double-run logic: the warmup is never recorded.
+            let warmup_runs = if std::env::var_os("RUSTC_PERF_WARMUP").is_some() {
+                1
+            } else {
+                0
+            };
+            for run in 0..warmup_runs + max_iterations {
+                // A cooperative stop only takes effect between runs, so the
+                // current run's database writes are never cut short.
+                if cancel.is_some_and(|token| token.is_cancelled()) {
+                    eprintln!("Cancellation requested; stopping {} cleanly", self.name);
+                    cancelled = true;
+                    break;
+                }
+                let is_warmup = run < warmup_runs;
+                processor.set_discard_results(is_warmup);
+                let i = run.saturating_sub(warmup_runs);
+                if !is_warmup && i == 1 {
                     let different = processor.finished_first_collection();
-                    if iterations == 1 && !different {
+                    if iterations == 1 && adaptive.is_none() && !different {
                         // Don't run twice if this processor doesn't need it and
                         // we've only been asked to run once.
                         break;
                     }
                 }
-                log::debug!("Benchmark iteration {}/{}", i + 1, iterations);
+                if is_warmup {
+                    log::debug!("Warmup iteration (results discarded)");
+                } else {
+                    log::debug!("Benchmark iteration {}/{}", i + 1, max_iterations);
+                }
                 // Don't delete the directory on error.
                 let timing_dir = ManuallyDrop::new(self.make_temp_dir(prep_dir.path())?);
                 let cwd = timing_dir.path();
 
+                // The 0-based iteration index reported to the processor's
+                // progress hooks (the patch loop below shadows `i`).
+                let iteration = i;
+
                 // A full non-incremental build.
                 if scenarios.contains(&Scenario::Full) {
+                    processor.on_iteration_start(profile, Scenario::Full, iteration);
                     self.mk_cargo_process(toolchain, cwd, profile, backend)
                         .processor(processor, Scenario::Full, "Full", None)
                         .run_rustc(true)
                         .await?;
+                    processor.on_iteration_complete(profile, Scenario::Full, iteration);
                 }
 
                 // Rustdoc does not support incremental compilation
@@ -376,30 +1275,48 @@ impl Benchmark {
                     // An incremental  from scratch (slowest incremental case).
                     // This is required for any subsequent incremental builds.
                     if scenarios.iter().any(|s| s.is_incr()) {
+                        processor.on_iteration_start(profile, Scenario::IncrFull, iteration);
                         self.mk_cargo_process(toolchain, cwd, profile, backend)
                             .incremental(true)
                             .processor(processor, Scenario::IncrFull, "IncrFull", None)
                             .run_rustc(true)
                             .await?;
+                        processor.on_iteration_complete(profile, Scenario::IncrFull, iteration);
                     }
 
                     // An incremental build with no changes (fastest incremental case).
                     if scenarios.contains(&Scenario::IncrUnchanged) {
+                        processor.on_iteration_start(profile, Scenario::IncrUnchanged, iteration);
                         self.mk_cargo_process(toolchain, cwd, profile, backend)
                             .incremental(true)
                             .processor(processor, Scenario::IncrUnchanged, "IncrUnchanged", None)
                             .run_rustc(true)
                             .await?;
+                        processor.on_iteration_complete(
+                            profile,
+                            Scenario::IncrUnchanged,
+                            iteration,
+                        );
                     }
 
-                    if scenarios.contains(&Scenario::IncrPatched) {
-                        for (i, patch) in self.patches.iter().enumerate() {
+                    // `IncrReverted` needs the incremental cache state left
+                    // behind by the patched build, so it implies `IncrPatched`
+                    // (which is then also recorded).
+                    if scenarios.contains(&Scenario::IncrPatched)
+                        || scenarios.contains(&Scenario::IncrReverted)
+                    {
+                        for (i, patch) in patches.iter().enumerate() {
                             log::debug!("applying patch {}", patch.name);
                             patch.apply(cwd).map_err(|s| anyhow::anyhow!("{}", s))?;
 
                             // An incremental build with some changes (realistic
                             // incremental case).
                             let scenario_str = format!("IncrPatched{}", i);
+                            processor.on_iteration_start(
+                                profile,
+                                Scenario::IncrPatched,
+                                iteration,
+                            );
                             self.mk_cargo_process(toolchain, cwd, profile, backend)
                                 .incremental(true)
                                 .processor(
@@ -410,10 +1327,80 @@ impl Benchmark {
                                 )
                                 .run_rustc(true)
                                 .await?;
+                            processor.on_iteration_complete(
+                                profile,
+                                Scenario::IncrPatched,
+                                iteration,
+                            );
+
+                            // Rebuilding after reverting the patch exercises a
+                            // different incremental path: previously cached
+                            // results are restored rather than recomputed.
+                            // Note that this also resets the tree, so the next
+                            // patch applies to the base sources again.
+                            if scenarios.contains(&Scenario::IncrReverted) {
+                                log::debug!("reverting patch {}", patch.name);
+                                patch.revert(cwd).map_err(|s| anyhow::anyhow!("{}", s))?;
+
+                                let scenario_str = format!("IncrReverted{}", i);
+                                processor.on_iteration_start(
+                                    profile,
+                                    Scenario::IncrReverted,
+                                    iteration,
+                                );
+                                self.mk_cargo_process(toolchain, cwd, profile, backend)
+                                    .incremental(true)
+                                    .processor(
+                                        processor,
+                                        Scenario::IncrReverted,
+                                        &scenario_str,
+                                        Some(patch),
+                                    )
+                                    .run_rustc(true)
+                                    .await?;
+                                processor.on_iteration_complete(
+                                    profile,
+                                    Scenario::IncrReverted,
+                                    iteration,
+                                );
+                            }
+
+                            // See `cumulative_patches`: in non-cumulative
+                            // mode the tree is reset, so the next patch
+                            // applies to the base sources (unless the
+                            // `IncrReverted` run above just did exactly
+                            // that).
+                            if !self.config.cumulative_patches
+                                && !scenarios.contains(&Scenario::IncrReverted)
+                            {
+                                log::debug!("reverting patch {}", patch.name);
+                                patch.revert(cwd).map_err(|s| anyhow::anyhow!("{}", s))?;
+                            }
                         }
                     }
                 }
                 timing_dirs.push(timing_dir);
+
+                // With an adaptive run count, stop as soon as the measurements
+                // are stable, but never before the requested minimum number of
+                // runs.
+                if let Some(adaptive) = adaptive {
+                    if i + 1 >= std::cmp::max(iterations, 2) {
+                        if let Some(cv) =
+                            coefficient_of_variation(processor.iteration_stat_samples())
+                        {
+                            if cv < adaptive.cv_threshold {
+                                log::debug!(
+                                    "stopping after {} iterations: coefficient of \
+                                     variation {:.4} is below the threshold",
+                                    i + 1,
+                                    cv
+                                );
+                                break;
+                            }
+                        }
+                    }
+                }
             }
         }
         log::trace!(
@@ -428,7 +1415,48 @@ impl Benchmark {
             drop(ManuallyDrop::into_inner(dir));
         }
 
-        Ok(())
+        Ok(if cancelled {
+            MeasureOutcome::Cancelled
+        } else {
+            MeasureOutcome::Completed
+        })
+    }
+
+    /// Measures this benchmark without a database and returns the gathered
+    /// statistics in memory: one entry per executed iteration of each
+    /// (profile, scenario) combination (and per patch for `IncrPatched`). A
+    /// convenience wrapper around `measure` with an `InMemoryProcessor`, for
+    /// embedding the collector in other tools.
+    pub async fn measure_in_memory(
+        &self,
+        profiles: &[Profile],
+        scenarios: &[Scenario],
+        backends: &[CodegenBackend],
+        toolchain: &Toolchain,
+        iterations: Option<usize>,
+    ) -> anyhow::Result<Vec<(Profile, Scenario, Stats)>> {
+        let mut processor = InMemoryProcessor::new();
+        self.measure(
+            &mut processor,
+            profiles,
+            scenarios,
+            backends,
+            toolchain,
+            iterations,
+            None,
+            None,
+            None,
+        )
+        .await?;
+        Ok(processor
+            .into_stats()
+            .into_iter()
+            .flat_map(|((profile, scenario), stats)| {
+                stats
+                    .into_iter()
+                    .map(move |stats| (profile, scenario, stats))
+            })
+            .collect())
     }
 }
 
@@ -496,7 +1524,13 @@ pub fn get_compile_benchmarks(
         }
 
         debug!("benchmark `{}`- registered", name);
-        benchmarks.push(Benchmark::new(name, path)?);
+        benchmarks.extend(
+            Benchmark::new(name, path)?
+                .into_crate_type_variants()
+                .into_iter()
+                .flat_map(Benchmark::into_lockfile_variants)
+                .flat_map(Benchmark::into_workspace_member_variants),
+        );
     }
 
     // All prefixes/suffixes must be used at least once. This is to catch typos.
@@ -532,6 +1566,30 @@ Expected zero or more entries or substrings from list: {:?}."#,
     Ok(benchmarks)
 }
 
+/// Deterministically shuffles the execution order of the given benchmarks
+/// based on `seed`.
+///
+/// Running benchmarks always in the same (alphabetical) order means that
+/// systematic machine-state effects (thermal, caches) always affect the same
+/// benchmarks. Shuffling with a recorded seed removes that bias while keeping
+/// the order reconstructible.
+pub fn shuffle_benchmarks(benchmarks: &mut [Benchmark], seed: u64) {
+    // xorshift64: we only need a reproducible shuffle, not cryptographic
+    // quality, and this avoids pulling in a PRNG dependency.
+    let mut state = seed | 1;
+    let mut next_random = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    // Fisher-Yates shuffle
+    for i in (1..benchmarks.len()).rev() {
+        let j = (next_random() % (i as u64 + 1)) as usize;
+        benchmarks.swap(i, j);
+    }
+}
+
 /// Helper to verify if a benchmark name matches a given substring, like a prefix or a suffix. The
 /// `predicate` closure will be passed each substring from `substrings` until it returns true, and
 /// in that case the substring's number of uses in the map will be increased.
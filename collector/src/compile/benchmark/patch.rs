@@ -1,14 +1,73 @@
 use crate::command_output;
+use anyhow::Context;
 use database::PatchName;
 use std::hash;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+#[derive(Debug, Clone)]
+enum PatchKind {
+    /// A committed `.patch` file applied with `git apply`.
+    File(PathBuf),
+    /// A synthetic patch that appends a trivial, uniquely named free function
+    /// to the end of the given source file.
+    AppendFunction(PathBuf),
+}
+
+/// Applies (or, with `reverse`, reverts) a `PatchKind::File` patch. `git
+/// apply` is tried first; plain unified diffs (`.diff` extension) and patches
+/// that `git apply` rejects — e.g. because the benchmark was imported from a
+/// plain tarball and the directory is not a usable git tree — go through the
+/// `patch` utility instead.
+fn apply_patch_file(
+    name: &PatchName,
+    dir: &Path,
+    patch_file: &Path,
+    reverse: bool,
+) -> anyhow::Result<()> {
+    let is_plain_diff = patch_file.extension().is_some_and(|ext| ext == "diff");
+    if !is_plain_diff {
+        let mut cmd = Command::new("git");
+        cmd.current_dir(dir).arg("apply");
+        if reverse {
+            cmd.arg("-R");
+        }
+        cmd.arg(patch_file);
+        match command_output(&mut cmd) {
+            Ok(_) => return Ok(()),
+            Err(error) => {
+                log::warn!(
+                    "`git apply` failed for patch {}: {:#}; falling back to `patch`",
+                    name,
+                    error
+                );
+            }
+        }
+    }
+
+    let mut cmd = Command::new("patch");
+    // `--strip=1` matches the `a/`/`b/` prefixes that both git-format patches
+    // and conventional unified diffs use.
+    cmd.current_dir(dir).args(["--strip=1", "--silent"]);
+    if reverse {
+        cmd.arg("--reverse");
+    }
+    cmd.arg("--input").arg(patch_file);
+    command_output(&mut cmd)
+        .with_context(|| format!("cannot apply patch {} with the `patch` utility", name))?;
+    Ok(())
+}
+
+/// The source text appended by `PatchKind::AppendFunction` patches. Kept as a
+/// single constant so that `revert` can remove exactly what `apply` added.
+const APPEND_FN_SNIPPET: &str =
+    "\n#[allow(dead_code)]\nfn rustc_perf_synthetic_append_fn() -> u32 {\n    42\n}\n";
+
 #[derive(Debug, Clone)]
 pub struct Patch {
     pub(crate) index: usize,
     pub name: PatchName,
-    path: PathBuf,
+    kind: PatchKind,
 }
 
 impl PartialEq for Patch {
@@ -51,19 +110,68 @@ impl Patch {
         };
 
         Patch {
-            path: PathBuf::from(path.file_name().unwrap().to_str().unwrap()),
+            kind: PatchKind::File(PathBuf::from(path.file_name().unwrap().to_str().unwrap())),
             index,
             name: name.as_str().into(),
         }
     }
 
+    /// Creates a synthetic patch that appends a trivial free function to the
+    /// end of `file` (a path relative to the benchmark directory), without
+    /// requiring a committed `.patch` file. The function is private, has a
+    /// crate-unique name and no dependencies, so it compiles in any crate
+    /// (including proc-macro crates, which forbid exporting other public
+    /// items). This exercises incremental compilation of a single new item
+    /// with maximal cache reuse everywhere else.
+    pub fn new_append_function(index: usize, file: PathBuf) -> Self {
+        Patch {
+            kind: PatchKind::AppendFunction(file),
+            index,
+            name: "append-fn".into(),
+        }
+    }
+
     pub fn apply(&self, dir: &Path) -> anyhow::Result<()> {
         log::debug!("applying {} to {:?}", self.name, dir);
 
-        let mut cmd = Command::new("git");
-        cmd.current_dir(dir).args(["apply"]).arg(&*self.path);
+        match &self.kind {
+            PatchKind::File(path) => {
+                apply_patch_file(&self.name, dir, path, false)?;
+            }
+            PatchKind::AppendFunction(file) => {
+                use std::io::Write;
 
-        command_output(&mut cmd)?;
+                let path = dir.join(file);
+                let mut file = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .with_context(|| format!("cannot open {:?} to append to", path))?;
+                file.write_all(APPEND_FN_SNIPPET.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reverses a previously applied patch, restoring the tree to its state
+    /// before `apply`. Used by the `IncrReverted` scenario.
+    pub fn revert(&self, dir: &Path) -> anyhow::Result<()> {
+        log::debug!("reverting {} in {:?}", self.name, dir);
+
+        match &self.kind {
+            PatchKind::File(path) => {
+                apply_patch_file(&self.name, dir, path, true)?;
+            }
+            PatchKind::AppendFunction(file) => {
+                let path = dir.join(file);
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("cannot read {:?} to revert", path))?;
+                let Some(stripped) = contents.strip_suffix(APPEND_FN_SNIPPET) else {
+                    anyhow::bail!("{:?} does not end with the appended function", path);
+                };
+                std::fs::write(&path, stripped)?;
+            }
+        }
 
         Ok(())
     }
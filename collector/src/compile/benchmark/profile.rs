@@ -12,6 +12,11 @@ pub enum Profile {
     Debug,
     Doc,
     Opt,
+    /// An optimized build (`--release`) that additionally emits line-tables
+    /// debuginfo (`-Cdebuginfo=1`), matching how rustc's own release binaries
+    /// are shipped. Debuginfo generation is a meaningful chunk of backend time
+    /// that `Opt` alone never measures.
+    OptDebuginfo,
     Clippy,
 }
 
@@ -22,7 +27,42 @@ impl Profile {
             Profile::Debug,
             Profile::Doc,
             Profile::Opt,
+            Profile::OptDebuginfo,
             Profile::Clippy,
         ]
     }
+
+    /// Parses a comma-separated list of profile names; the string `All`
+    /// selects every variant. This mirrors the collector's CLI syntax, for
+    /// external harnesses that drive `Benchmark::measure` directly.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::all());
+        }
+        s.split(',').map(|item| item.trim().parse()).collect()
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::str::FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|profile| format!("{:?}", profile).eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let valid = Self::all()
+                    .into_iter()
+                    .map(|profile| format!("{:?}", profile))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("'{s}' is not a valid profile; expected one of {valid}")
+            })
+    }
 }
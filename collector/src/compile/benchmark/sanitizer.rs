@@ -0,0 +1,38 @@
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[value(rename_all = "kebab-case")]
+pub enum Sanitizer {
+    Asan,
+    Lsan,
+    Msan,
+    Tsan,
+}
+
+impl Sanitizer {
+    /// Returns the `-Zsanitizer` flag that enables this sanitizer for the
+    /// leaf rustc.
+    ///
+    /// The flag is only injected into the final crate; its dependencies and
+    /// the standard library are built without instrumentation. That is enough
+    /// for measuring the sanitizer's compile-time cost, but producing a
+    /// *runnable* sanitized binary usually additionally requires
+    /// `-Zbuild-std` and a supported target.
+    pub fn rustc_flag(&self) -> &'static str {
+        match self {
+            Sanitizer::Asan => "-Zsanitizer=address",
+            Sanitizer::Lsan => "-Zsanitizer=leak",
+            Sanitizer::Msan => "-Zsanitizer=memory",
+            Sanitizer::Tsan => "-Zsanitizer=thread",
+        }
+    }
+
+    /// A short tag appended to benchmark names so that sanitized measurements
+    /// are recorded under a distinct label.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Sanitizer::Asan => "asan",
+            Sanitizer::Lsan => "lsan",
+            Sanitizer::Msan => "msan",
+            Sanitizer::Tsan => "tsan",
+        }
+    }
+}
@@ -5,6 +5,10 @@ pub enum Scenario {
     IncrFull,
     IncrUnchanged,
     IncrPatched,
+    /// An incremental build after *reverting* a previously applied patch,
+    /// which restores previously cached results rather than recomputing them.
+    /// Implies `IncrPatched`: each patch is built (and recorded) first.
+    IncrReverted,
 }
 
 impl Scenario {
@@ -14,6 +18,7 @@ impl Scenario {
             Scenario::IncrFull,
             Scenario::IncrUnchanged,
             Scenario::IncrPatched,
+            Scenario::IncrReverted,
         ]
     }
 
@@ -21,10 +26,47 @@ impl Scenario {
         vec![Scenario::Full]
     }
 
+    /// Parses a comma-separated list of scenario names; the string `All`
+    /// selects every variant. This mirrors the collector's CLI syntax, for
+    /// external harnesses that drive `Benchmark::measure` directly.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::all());
+        }
+        s.split(',').map(|item| item.trim().parse()).collect()
+    }
+
     pub fn is_incr(self) -> bool {
         matches!(
             self,
-            Scenario::IncrFull | Scenario::IncrUnchanged | Scenario::IncrPatched
+            Scenario::IncrFull
+                | Scenario::IncrUnchanged
+                | Scenario::IncrPatched
+                | Scenario::IncrReverted
         )
     }
 }
+
+impl std::fmt::Display for Scenario {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::str::FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::all()
+            .into_iter()
+            .find(|scenario| format!("{:?}", scenario).eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let valid = Self::all()
+                    .into_iter()
+                    .map(|scenario| format!("{:?}", scenario))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("'{s}' is not a valid scenario; expected one of {valid}")
+            })
+    }
+}
@@ -3,6 +3,7 @@ use crate::compile::benchmark::profile::Profile;
 use crate::compile::benchmark::scenario::Scenario;
 use crate::compile::benchmark::BenchmarkName;
 use crate::compile::execute;
+use crate::compile::execute::stat_transform::StatTransform;
 use crate::compile::execute::{
     rustc, DeserializeStatError, PerfTool, ProcessOutputData, Processor, Retry, SelfProfileFiles,
     Stats,
@@ -13,14 +14,84 @@ use anyhow::Context;
 use database::CollectionId;
 use futures::stream::FuturesUnordered;
 use futures::{future, StreamExt};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::process::Command;
 use std::{env, process};
 
+/// Maximum number of attempts to gather valid statistics for a single rustc
+/// invocation before giving up. Can be overridden with the
+/// `RUSTC_PERF_MAX_STAT_RETRIES` environment variable.
+const DEFAULT_MAX_TRIES: u8 = 5;
+
+fn max_tries() -> u8 {
+    env::var("RUSTC_PERF_MAX_STAT_RETRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TRIES)
+}
+
+/// A patch whose incremental rebuild is at least this many times more
+/// expensive than the median patch of the same benchmark is reported as a
+/// cliff. Can be overridden with the `RUSTC_PERF_CLIFF_THRESHOLD` environment
+/// variable.
+const DEFAULT_CLIFF_THRESHOLD: f64 = 1.5;
+
+fn cliff_threshold() -> f64 {
+    env::var("RUSTC_PERF_CLIFF_THRESHOLD")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_CLIFF_THRESHOLD)
+}
+
+/// A short excerpt of a child's stdout/stderr for log messages.
+fn output_snippet(output: &process::Output) -> String {
+    fn excerpt(stream: &[u8]) -> String {
+        let text = String::from_utf8_lossy(stream);
+        let text = text.trim();
+        if text.chars().count() > 200 {
+            let mut excerpt: String = text.chars().take(200).collect();
+            excerpt.push_str("...");
+            excerpt
+        } else {
+            text.to_string()
+        }
+    }
+    format!(
+        "stdout: {:?}, stderr: {:?}",
+        excerpt(&output.stdout),
+        excerpt(&output.stderr)
+    )
+}
+
+/// Writes the full stdout/stderr of a failed benchmark run to a log file in
+/// the system temp directory, named after the benchmark. The compile error
+/// that made the build produce no stats usually lives in stderr, which would
+/// otherwise survive only as a truncated excerpt in the log. Returns the path
+/// on success.
+fn persist_failure_log(benchmark: &BenchmarkName, output: &process::Output) -> Option<PathBuf> {
+    let path = env::temp_dir().join(format!("rustc-perf-{}.log", benchmark));
+    let mut contents = Vec::new();
+    contents.extend_from_slice(b"--- stdout ---\n");
+    contents.extend_from_slice(&output.stdout);
+    contents.extend_from_slice(b"\n--- stderr ---\n");
+    contents.extend_from_slice(&output.stderr);
+    match std::fs::write(&path, contents) {
+        Ok(()) => Some(path),
+        Err(error) => {
+            log::warn!(
+                "failed to write failure log to {}: {:?}",
+                path.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
 pub struct RecordedSelfProfile {
     collection: CollectionId,
     scenario: database::Scenario,
@@ -35,6 +106,44 @@ pub enum Bencher {
     PerfStatSelfProfile,
     XperfStat,
     XperfStatSelfProfile,
+    /// Plain timing plus `getrusage`, used on macOS where `perf` is not
+    /// available. Only collects a reduced stat set (wall-time, max-rss).
+    TimeStat,
+}
+
+/// How statistics gathered across iterations are recorded into the database.
+///
+/// By default every iteration's value is recorded separately and aggregation
+/// happens at query time. The other modes buffer the per-iteration values and
+/// record a single aggregated value per statistic instead. The minimum is
+/// usually the most reproducible estimator for hardware counters such as
+/// `instructions:u`, since noise only ever adds work; the median is better
+/// suited for noisy, wall-time-like metrics.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum StatAggregation {
+    Min,
+    Median,
+    Mean,
+}
+
+impl StatAggregation {
+    /// Aggregates a non-empty set of per-iteration values into a single one.
+    fn aggregate(&self, values: &mut [f64]) -> f64 {
+        assert!(!values.is_empty());
+        values.sort_by(|a, b| a.total_cmp(b));
+        match self {
+            StatAggregation::Min => values[0],
+            StatAggregation::Median => {
+                let mid = values.len() / 2;
+                if values.len() % 2 == 0 {
+                    (values[mid - 1] + values[mid]) / 2.0
+                } else {
+                    values[mid]
+                }
+            }
+            StatAggregation::Mean => values.iter().sum::<f64>() / values.len() as f64,
+        }
+    }
 }
 
 pub struct BenchProcessor<'a> {
@@ -44,7 +153,71 @@ pub struct BenchProcessor<'a> {
     artifact_row_id: database::ArtifactIdNumber,
     is_first_collection: bool,
     is_self_profile: bool,
+    stat_transform: StatTransform,
+    /// When set, the per-iteration statistics are additionally exported into
+    /// a Criterion-compatible directory layout rooted at this path.
+    criterion_export: Option<PathBuf>,
+    /// Per-iteration samples gathered for the Criterion export, keyed by
+    /// (profile, scenario, statistic).
+    samples: HashMap<(String, String, String), Vec<f64>>,
+    /// When set, per-iteration values are buffered here instead of being
+    /// recorded immediately, and a single aggregated value per statistic is
+    /// recorded in `postprocess_results`.
+    aggregation: Option<StatAggregation>,
+    /// When set, every completed measurement is additionally written to
+    /// stdout as a single JSON line, so the collector can be piped into other
+    /// tools in real time.
+    json_stdout: bool,
+    buffered: HashMap<
+        (
+            database::Profile,
+            database::Scenario,
+            database::CodegenBackend,
+            String,
+        ),
+        Vec<f64>,
+    >,
     tries: u8,
+    max_tries: u8,
+    /// Profiles for which the effective `--emit` set has already been stored
+    /// as collection metadata, so that it is only recorded once per profile.
+    recorded_emits: Vec<database::Profile>,
+    /// Profiles for which the crate metadata hash has already been stored.
+    recorded_crate_metadata: Vec<database::Profile>,
+    /// Whether results processed right now belong to a warmup iteration and
+    /// should be thrown away instead of recorded.
+    discard_results: bool,
+    /// Whether to additionally record every iteration's value under an
+    /// iteration-indexed metric name (`RUSTC_PERF_ITERATION_STATS`), so that
+    /// the per-iteration distribution stays visible even when `aggregation`
+    /// collapses the regular series into a single value.
+    iteration_indexed: bool,
+    /// Number of iterations recorded so far per (profile, scenario, backend),
+    /// used to index the iteration-indexed metric names.
+    iteration_counts: HashMap<
+        (
+            database::Profile,
+            database::Scenario,
+            database::CodegenBackend,
+        ),
+        u32,
+    >,
+    /// Whether to record per-invocation start/end timestamps as collection
+    /// metadata (`RUSTC_PERF_RECORD_TIMESTAMPS`), for reconstructing a
+    /// timeline of when each measurement happened.
+    record_timestamps: bool,
+    /// Number of timestamped invocations so far per (profile, scenario), used
+    /// to give each measurement a distinct metadata key.
+    timestamp_counts: HashMap<String, u32>,
+    /// Per-iteration values of the primary stat of the `Full` scenario,
+    /// reported through `iteration_stat_samples` so that an adaptive run
+    /// count can stop once the measurements are stable. Cleared at the start
+    /// of each collection.
+    iteration_stats: Vec<f64>,
+    /// Per-patch primary metric gathered across `IncrPatched` runs, used at
+    /// the end of the benchmark to flag patches whose incremental rebuild is
+    /// disproportionately more expensive than the others.
+    patch_stats: HashMap<(database::Profile, CodegenBackend), HashMap<String, Vec<f64>>>,
     self_profiles: Vec<RecordedSelfProfile>,
 }
 
@@ -55,9 +228,15 @@ impl<'a> BenchProcessor<'a> {
         artifact: &'a database::ArtifactId,
         artifact_row_id: database::ArtifactIdNumber,
         is_self_profile: bool,
+        stat_transform: StatTransform,
+        criterion_export: Option<PathBuf>,
+        aggregation: Option<StatAggregation>,
+        json_stdout: bool,
     ) -> Self {
         // Check we have `perf` or (`xperf.exe` and `tracelog.exe`)  available.
-        if cfg!(unix) {
+        if cfg!(target_os = "macos") {
+            // Plain timing plus `getrusage`; no external tool required.
+        } else if cfg!(unix) {
             let has_perf = Command::new("perf").output().is_ok();
             assert!(has_perf);
         } else {
@@ -80,7 +259,23 @@ impl<'a> BenchProcessor<'a> {
             artifact_row_id,
             is_first_collection: true,
             is_self_profile,
+            stat_transform,
+            criterion_export,
+            samples: HashMap::new(),
+            aggregation,
+            json_stdout,
+            buffered: HashMap::new(),
             tries: 0,
+            max_tries: max_tries(),
+            recorded_emits: vec![],
+            recorded_crate_metadata: vec![],
+            discard_results: false,
+            iteration_indexed: env::var_os("RUSTC_PERF_ITERATION_STATS").is_some(),
+            iteration_counts: HashMap::new(),
+            record_timestamps: env::var_os("RUSTC_PERF_RECORD_TIMESTAMPS").is_some(),
+            timestamp_counts: HashMap::new(),
+            iteration_stats: vec![],
+            patch_stats: HashMap::new(),
             self_profiles: vec![],
         }
     }
@@ -98,6 +293,66 @@ impl<'a> BenchProcessor<'a> {
             CodegenBackend::Cranelift => database::CodegenBackend::Cranelift,
         };
 
+        if self.json_stdout {
+            emit_json_line(self.benchmark, profile, scenario, &stats);
+        }
+
+        if scenario == database::Scenario::Empty {
+            if let Some(value) = stats
+                .get("instructions:u")
+                .or_else(|| stats.get("wall-time"))
+            {
+                self.iteration_stats.push(value);
+            }
+        }
+
+        if self.criterion_export.is_some() {
+            for (stat, value) in stats.iter() {
+                self.samples
+                    .entry((profile.to_string(), scenario.to_id(), stat.to_string()))
+                    .or_default()
+                    .push(value);
+            }
+        }
+
+        if self.iteration_indexed {
+            // Record each value a second time under an iteration-indexed
+            // metric name. These series survive `aggregation` (which only
+            // collapses the regular series), so the full per-iteration
+            // distribution remains available to regression analysis.
+            let iteration = self
+                .iteration_counts
+                .entry((profile, scenario, backend))
+                .or_insert(0);
+            let index = *iteration;
+            *iteration += 1;
+            for (stat, value) in stats.iter() {
+                let metric = format!("{stat}:iter-{index}");
+                self.conn
+                    .record_statistic(
+                        collection,
+                        self.artifact_row_id,
+                        self.benchmark.0.as_str(),
+                        profile,
+                        scenario,
+                        backend,
+                        &metric,
+                        value,
+                    )
+                    .await;
+            }
+        }
+
+        if self.aggregation.is_some() {
+            for (stat, value) in stats.iter() {
+                self.buffered
+                    .entry((profile, scenario, backend, stat.to_string()))
+                    .or_default()
+                    .push(value);
+            }
+            return;
+        }
+
         let mut buf = FuturesUnordered::new();
         for (stat, value) in stats.iter() {
             buf.push(self.conn.record_statistic(
@@ -122,7 +377,11 @@ impl<'a> BenchProcessor<'a> {
 
 impl<'a> Processor for BenchProcessor<'a> {
     fn perf_tool(&self) -> PerfTool {
-        if self.is_first_collection && self.is_self_profile {
+        if cfg!(target_os = "macos") {
+            // Hardware counters are not accessible through `perf` on macOS,
+            // so only the reduced stat set is collected there.
+            PerfTool::BenchTool(Bencher::TimeStat)
+        } else if self.is_first_collection && self.is_self_profile {
             if cfg!(unix) {
                 PerfTool::BenchTool(Bencher::PerfStatSelfProfile)
             } else {
@@ -137,6 +396,11 @@ impl<'a> Processor for BenchProcessor<'a> {
 
     fn start_first_collection(&mut self) {
         self.is_first_collection = true;
+        self.iteration_stats.clear();
+    }
+
+    fn iteration_stat_samples(&self) -> &[f64] {
+        &self.iteration_stats
     }
 
     fn finished_first_collection(&mut self) -> bool {
@@ -146,6 +410,10 @@ impl<'a> Processor for BenchProcessor<'a> {
         self.perf_tool() != original
     }
 
+    fn set_discard_results(&mut self, discard: bool) {
+        self.discard_results = discard;
+    }
+
     fn process_output<'b>(
         &'b mut self,
         data: &'b ProcessOutputData<'_>,
@@ -154,8 +422,19 @@ impl<'a> Processor for BenchProcessor<'a> {
         Box::pin(async move {
             match execute::process_stat_output(output) {
                 Ok(mut res) => {
+                    // Warmup iteration: the run only serves to warm disk and
+                    // page caches, so nothing is recorded.
+                    if self.discard_results {
+                        return Ok(Retry::No);
+                    }
                     if let Some(ref profile) = res.1 {
                         execute::store_artifact_sizes_into_stats(&mut res.0, profile);
+                        execute::store_query_cache_hit_rate_into_stats(&mut res.0, profile);
+                        execute::store_incr_cache_load_time_into_stats(
+                            &mut res.0,
+                            profile,
+                            data.scenario,
+                        );
                     }
                     if let Profile::Doc = data.profile {
                         let doc_dir = data.cwd.join("target/doc");
@@ -163,6 +442,29 @@ impl<'a> Processor for BenchProcessor<'a> {
                             execute::store_documentation_size_into_stats(&mut res.0, &doc_dir);
                         }
                     }
+                    // Per-section artifact sizes (opt-in): only profiles that
+                    // actually codegen produce meaningful sections.
+                    if env::var_os("RUSTC_PERF_SECTION_SIZES").is_some()
+                        && matches!(
+                            data.profile,
+                            Profile::Debug | Profile::Opt | Profile::OptDebuginfo
+                        )
+                    {
+                        execute::store_section_sizes_into_stats(&mut res.0, data.cwd, data.profile);
+                    }
+                    // The incremental cache only exists (and only changes)
+                    // for incremental scenarios.
+                    if data.scenario.is_incr() {
+                        execute::store_incremental_cache_size_into_stats(&mut res.0, data.cwd);
+                    }
+
+                    // Ratios derived from the hardware counters, computed
+                    // once here instead of by every downstream consumer.
+                    execute::store_derived_stats(&mut res.0);
+
+                    // Apply the user-supplied declarative transform (renames,
+                    // unit conversions, derived stats) before recording.
+                    self.stat_transform.apply(&mut res.0);
 
                     let scenario = match data.scenario {
                         Scenario::Full => database::Scenario::Empty,
@@ -172,18 +474,99 @@ impl<'a> Processor for BenchProcessor<'a> {
                             let patch = data.patch.unwrap();
                             database::Scenario::IncrementalPatch(patch.name)
                         }
+                        Scenario::IncrReverted => {
+                            let patch = data.patch.unwrap();
+                            database::Scenario::IncrementalPatch(
+                                format!("{} (reverted)", patch.name).as_str().into(),
+                            )
+                        }
                     };
                     let profile = match data.profile {
                         Profile::Check => database::Profile::Check,
                         Profile::Debug => database::Profile::Debug,
                         Profile::Doc => database::Profile::Doc,
                         Profile::Opt => database::Profile::Opt,
+                        Profile::OptDebuginfo => database::Profile::OptDebuginfo,
                         Profile::Clippy => database::Profile::Clippy,
                     };
 
+                    if let (Scenario::IncrPatched, Some(patch)) = (data.scenario, data.patch) {
+                        let primary = res
+                            .0
+                            .iter()
+                            .find(|(stat, _)| *stat == "instructions:u")
+                            .or_else(|| res.0.iter().find(|(stat, _)| *stat == "wall-time"));
+                        if let Some((_, value)) = primary {
+                            self.patch_stats
+                                .entry((profile, data.backend))
+                                .or_default()
+                                .entry(patch.name.to_string())
+                                .or_default()
+                                .push(value);
+                        }
+                    }
+
                     let version = get_rustc_perf_commit();
                     let collection = self.conn.collection_id(&version).await;
 
+                    // Record the effective `--emit` set for this profile so
+                    // that it is clear what work the measured invocation
+                    // actually performed.
+                    if !self.recorded_emits.contains(&profile) {
+                        let emit = self.perf_tool().emit_types(data.profile);
+                        self.conn
+                            .record_collection_metadata(
+                                self.artifact_row_id,
+                                &format!("emit:{profile}"),
+                                emit,
+                            )
+                            .await;
+                        self.recorded_emits.push(profile);
+                    }
+
+                    // Timestamps pair the real-time start with a
+                    // monotonically measured duration, so the end is
+                    // orderable even across clock adjustments while both
+                    // stay human-meaningful.
+                    if self.record_timestamps {
+                        let key = format!("{profile}:{}", data.scenario_str);
+                        let n = {
+                            let count = self.timestamp_counts.entry(key).or_insert(0);
+                            *count += 1;
+                            *count
+                        };
+                        let end = data.start
+                            + chrono::Duration::from_std(data.duration).unwrap_or_default();
+                        self.conn
+                            .record_collection_metadata(
+                                self.artifact_row_id,
+                                &format!(
+                                    "timestamps:{}:{profile}:{}:{n}",
+                                    self.benchmark, data.scenario_str
+                                ),
+                                &format!("{}/{}", data.start.to_rfc3339(), end.to_rfc3339()),
+                            )
+                            .await;
+                    }
+
+                    // The `-Cmetadata` hash gives the emitted artifact a
+                    // canonical identity that is stable across runs with
+                    // identical inputs, so artifacts of two runs can be
+                    // definitively matched (see `print_crate_metadata` in
+                    // `rustc-fake`).
+                    if let Some(metadata) = &res.3 {
+                        if !self.recorded_crate_metadata.contains(&profile) {
+                            self.conn
+                                .record_collection_metadata(
+                                    self.artifact_row_id,
+                                    &format!("crate-metadata:{}:{profile}", self.benchmark),
+                                    metadata,
+                                )
+                                .await;
+                            self.recorded_crate_metadata.push(profile);
+                        }
+                    }
+
                     if let Some(files) = res.2 {
                         self.self_profiles.push(RecordedSelfProfile {
                             collection,
@@ -195,8 +578,13 @@ impl<'a> Processor for BenchProcessor<'a> {
                         // If the gathered metrics were produced with self profile enabled, then they
                         // are not realistic. Do not store the metrics that are affected by
                         // self-profiling into the DB for self-profile runs to avoid unnecessary
-                        // DB storage.
-                        res.0.stats.retain(|key, _| key.starts_with("size:"));
+                        // DB storage. Stats that are derived *from* the self-profile data itself
+                        // (like the query cache hit rate) are kept.
+                        res.0.stats.retain(|key, _| {
+                            key.starts_with("size:")
+                                || key == "query-cache-hit-rate"
+                                || key == "incr-cache-load-time"
+                        });
                     }
 
                     self.insert_stats(collection, scenario, profile, data.backend, res.0)
@@ -205,32 +593,103 @@ impl<'a> Processor for BenchProcessor<'a> {
                     Ok(Retry::No)
                 }
                 Err(DeserializeStatError::NoOutput(output)) => {
-                    if self.tries < 5 {
+                    if self.tries < self.max_tries {
+                        self.tries += 1;
                         log::warn!(
-                            "failed to deserialize stats, retrying (try {}); output: {:?}",
+                            "failed to deserialize stats for {} ({:?}, {}), retrying (try {}/{}); {}",
+                            data.name,
+                            data.profile,
+                            data.scenario_str,
                             self.tries,
-                            output
+                            self.max_tries,
+                            output_snippet(&output)
                         );
+                        Ok(Retry::Yes)
+                    } else {
+                        let log_file = persist_failure_log(&data.name, &output);
+                        Err(anyhow::anyhow!(
+                            "failed to collect statistics for {} ({:?}, {}) after {} tries; {}{}",
+                            data.name,
+                            data.profile,
+                            data.scenario_str,
+                            self.max_tries,
+                            output_snippet(&output),
+                            log_file
+                                .map(|path| format!("; full output saved to {}", path.display()))
+                                .unwrap_or_default()
+                        ))
+                    }
+                }
+                Err(DeserializeStatError::ImplausibleValue { stat, value }) => {
+                    if self.tries < self.max_tries {
                         self.tries += 1;
+                        log::warn!(
+                            "implausible value {} for statistic `{}` of {} ({:?}, {}), \
+                             retrying (try {}/{})",
+                            value,
+                            stat,
+                            data.name,
+                            data.profile,
+                            data.scenario_str,
+                            self.tries,
+                            self.max_tries
+                        );
                         Ok(Retry::Yes)
                     } else {
-                        panic!("failed to collect statistics after 5 tries");
+                        Err(anyhow::anyhow!(
+                            "failed to collect plausible statistics for {} ({:?}, {}) after {} tries",
+                            data.name,
+                            data.profile,
+                            data.scenario_str,
+                            self.max_tries
+                        ))
                     }
                 }
                 Err(
                     e @ (DeserializeStatError::ParseError { .. }
                     | DeserializeStatError::XperfError(..)
                     | DeserializeStatError::IOError(..)),
-                ) => {
-                    panic!("process_perf_stat_output failed: {:?}", e);
-                }
+                ) => Err(anyhow::anyhow!("process_perf_stat_output failed: {:?}", e)),
             }
         })
     }
 
     fn postprocess_results<'b>(&'b mut self) -> Pin<Box<dyn Future<Output = ()> + 'b>> {
         Box::pin(async move {
-            if env::var_os("RUSTC_PERF_UPLOAD_TO_S3").is_some() {
+            if let Some(aggregation) = self.aggregation {
+                let version = get_rustc_perf_commit();
+                let collection = self.conn.collection_id(&version).await;
+                for ((profile, scenario, backend, stat), mut values) in
+                    std::mem::take(&mut self.buffered)
+                {
+                    let value = aggregation.aggregate(&mut values);
+                    self.conn
+                        .record_statistic(
+                            collection,
+                            self.artifact_row_id,
+                            self.benchmark.0.as_str(),
+                            profile,
+                            scenario,
+                            backend,
+                            &stat,
+                            value,
+                        )
+                        .await;
+                }
+            }
+
+            report_patch_cliffs(self.benchmark, &self.patch_stats);
+
+            if let Some(root) = &self.criterion_export {
+                if let Err(error) = write_criterion_export(root, self.benchmark, &self.samples) {
+                    eprintln!(
+                        "collector error: failed to write criterion export: {:#}",
+                        error
+                    );
+                }
+            }
+
+            if upload_enabled() {
                 let futs = self
                     .self_profiles
                     .iter()
@@ -246,8 +705,9 @@ impl<'a> Processor for BenchProcessor<'a> {
                     .collect::<Vec<_>>();
                 future::join_all(futs).await;
 
-                // Upload profiles to S3. Buffer up to 10 uploads at a time.
-                let mut uploads: VecDeque<SelfProfileS3Upload> = VecDeque::new();
+                // Upload profiles to the configured storage backend. Buffer
+                // up to 10 uploads at a time.
+                let mut uploads: VecDeque<SelfProfileUpload> = VecDeque::new();
                 for profile in self.self_profiles.drain(..) {
                     if uploads.len() == 10 {
                         uploads.pop_front().unwrap().wait();
@@ -260,7 +720,7 @@ impl<'a> Processor for BenchProcessor<'a> {
                         .join(profile.profile.to_string())
                         .join(profile.scenario.to_id());
                     let upload =
-                        SelfProfileS3Upload::new(prefix, profile.collection, profile.files);
+                        SelfProfileUpload::new(prefix, profile.collection, profile.files);
                     uploads.push_back(upload);
                 }
                 for upload in uploads {
@@ -271,19 +731,415 @@ impl<'a> Processor for BenchProcessor<'a> {
     }
 }
 
-/// Uploads self-profile results to S3
-struct SelfProfileS3Upload(
-    std::process::Child,
-    // This field is used only for its Drop impl
-    #[allow(unused)] tempfile::NamedTempFile,
-);
+/// Processor that collects the statistics of each benchmarked configuration
+/// into memory, keyed by (profile, scenario), instead of storing them into a
+/// database. This makes it possible to run a benchmark through
+/// `Benchmark::measure` and examine the gathered `Stats` afterwards, e.g. for
+/// tests or when using the collector as a library.
+#[derive(Default)]
+pub struct InMemoryProcessor {
+    tries: u8,
+    discard_results: bool,
+    stats: HashMap<(Profile, Scenario), Vec<Stats>>,
+}
+
+impl InMemoryProcessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the statistics collected so far.
+    /// There can be multiple entries recorded for a single (profile, scenario)
+    /// combination: one per executed iteration (and per patch for
+    /// `IncrPatched`).
+    pub fn stats(&self) -> &HashMap<(Profile, Scenario), Vec<Stats>> {
+        &self.stats
+    }
+
+    pub fn into_stats(self) -> HashMap<(Profile, Scenario), Vec<Stats>> {
+        self.stats
+    }
+}
+
+impl Processor for InMemoryProcessor {
+    fn perf_tool(&self) -> PerfTool {
+        if cfg!(target_os = "macos") {
+            PerfTool::BenchTool(Bencher::TimeStat)
+        } else if cfg!(unix) {
+            PerfTool::BenchTool(Bencher::PerfStat)
+        } else {
+            PerfTool::BenchTool(Bencher::XperfStat)
+        }
+    }
+
+    fn set_discard_results(&mut self, discard: bool) {
+        self.discard_results = discard;
+    }
 
-impl SelfProfileS3Upload {
+    fn process_output<'b>(
+        &'b mut self,
+        data: &'b ProcessOutputData<'_>,
+        output: process::Output,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<Retry>> + 'b>> {
+        Box::pin(async move {
+            match execute::process_stat_output(output) {
+                Ok((stats, ..)) => {
+                    if !self.discard_results {
+                        self.stats
+                            .entry((data.profile, data.scenario))
+                            .or_default()
+                            .push(stats);
+                    }
+                    Ok(Retry::No)
+                }
+                Err(
+                    error @ (DeserializeStatError::NoOutput(_)
+                    | DeserializeStatError::ImplausibleValue { .. }),
+                ) => {
+                    if self.tries < 5 {
+                        log::warn!("failed to gather stats, retrying (try {}): {}", self.tries, error);
+                        self.tries += 1;
+                        Ok(Retry::Yes)
+                    } else {
+                        Err(anyhow::anyhow!(
+                            "failed to collect statistics after 5 tries: {}",
+                            error
+                        ))
+                    }
+                }
+                Err(error) => Err(error.into()),
+            }
+        })
+    }
+}
+
+/// Writes one completed measurement to stdout as a single JSON line and
+/// flushes it immediately, so a consumer piping the collector into another
+/// tool sees progress live. All human-readable logging goes to stderr, which
+/// keeps stdout pure data. Integer counters are emitted as JSON integers, so
+/// they round-trip exactly.
+fn emit_json_line(
+    benchmark: &BenchmarkName,
+    profile: database::Profile,
+    scenario: database::Scenario,
+    stats: &Stats,
+) {
+    let line = serde_json::json!({
+        "benchmark": benchmark.0,
+        "profile": profile.to_string(),
+        "scenario": scenario.to_id(),
+        "stats": stats,
+    });
+    let mut stdout = std::io::stdout().lock();
+    if let Err(error) = writeln!(stdout, "{line}").and_then(|()| stdout.flush()) {
+        log::warn!("failed to write JSON line to stdout: {error}");
+    }
+}
+
+/// Prints a summary of the per-patch cost of the `IncrPatched` runs of one
+/// benchmark and flags patches that are disproportionately more expensive
+/// than the median patch ("cliffs"). The whole point of running many small
+/// edits is to find the expensive one, so surface it directly instead of
+/// leaving it to manual inspection of the recorded statistics.
+///
+/// The primary metric is `instructions:u` where available (falling back to
+/// `wall-time`), averaged over iterations. A patch is flagged when its value
+/// is at least `RUSTC_PERF_CLIFF_THRESHOLD` (default 1.5) times the median.
+fn report_patch_cliffs(
+    benchmark: &BenchmarkName,
+    patch_stats: &HashMap<(database::Profile, CodegenBackend), HashMap<String, Vec<f64>>>,
+) {
+    let threshold = cliff_threshold();
+    let mut groups: Vec<_> = patch_stats.iter().collect();
+    groups.sort_by_key(|((profile, backend), _)| (profile.to_string(), format!("{backend:?}")));
+    for ((profile, backend), patches) in groups {
+        // Comparing a single patch against itself is meaningless.
+        if patches.len() < 2 {
+            continue;
+        }
+        let mut averaged: Vec<(&str, f64)> = patches
+            .iter()
+            .map(|(name, values)| {
+                (
+                    name.as_str(),
+                    values.iter().sum::<f64>() / values.len() as f64,
+                )
+            })
+            .collect();
+        let median = {
+            let mut values: Vec<f64> = averaged.iter().map(|(_, value)| *value).collect();
+            values.sort_by(|a, b| a.total_cmp(b));
+            values[values.len() / 2]
+        };
+        averaged.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        eprintln!("incremental patch summary for {benchmark} ({profile}, {backend:?}):");
+        for (name, value) in averaged {
+            let ratio = value / median;
+            if ratio >= threshold {
+                eprintln!("  {name}: {value:.0} ({ratio:.2}x the median patch) <- cliff");
+            } else {
+                eprintln!("  {name}: {value:.0} ({ratio:.2}x the median patch)");
+            }
+        }
+    }
+}
+
+/// Writes the gathered per-iteration samples of one benchmark in the
+/// directory/JSON layout produced by Criterion, so that existing
+/// Criterion-based analysis tooling can ingest them directly.
+///
+/// The benchmark id is `<benchmark>/<profile>/<scenario>/<statistic>` (with
+/// the database representations of profile and scenario, e.g.
+/// `ripgrep-13.0.0/check/full/instructions:u`). For each id, the
+/// `new/estimates.json` file contains the mean and standard deviation and
+/// `new/sample.json` contains the raw per-iteration values.
+fn write_criterion_export(
+    root: &Path,
+    benchmark: &BenchmarkName,
+    samples: &HashMap<(String, String, String), Vec<f64>>,
+) -> anyhow::Result<()> {
+    for ((profile, scenario, stat), values) in samples {
+        let dir = root
+            .join(&benchmark.0)
+            .join(profile)
+            .join(scenario)
+            .join(stat)
+            .join("new");
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create criterion export dir {:?}", dir))?;
+
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let std_dev = if count > 1 {
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let standard_error = std_dev / (count as f64).sqrt();
+
+        let estimate = |point: f64, standard_error: f64| {
+            serde_json::json!({
+                "confidence_interval": {
+                    "confidence_level": 0.95,
+                    "lower_bound": point - 1.96 * standard_error,
+                    "upper_bound": point + 1.96 * standard_error,
+                },
+                "point_estimate": point,
+                "standard_error": standard_error,
+            })
+        };
+        let estimates = serde_json::json!({
+            "mean": estimate(mean, standard_error),
+            "std_dev": estimate(std_dev, 0.0),
+        });
+        std::fs::write(
+            dir.join("estimates.json"),
+            serde_json::to_string_pretty(&estimates)?,
+        )?;
+
+        let sample = serde_json::json!({
+            "sampling_mode": "Flat",
+            "iters": vec![1.0; count],
+            "times": values,
+        });
+        std::fs::write(dir.join("sample.json"), serde_json::to_string_pretty(&sample)?)?;
+
+        let benchmark_meta = serde_json::json!({
+            "group_id": benchmark.0,
+            "function_id": profile,
+            "value_str": format!("{scenario}/{stat}"),
+            "throughput": null,
+            "full_id": format!("{}/{profile}/{scenario}/{stat}", benchmark.0),
+        });
+        std::fs::write(
+            dir.join("benchmark.json"),
+            serde_json::to_string_pretty(&benchmark_meta)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// An upload that has been started and may still be running.
+enum UploadInFlight {
+    /// A backend subprocess (e.g. `gsutil`).
+    Child(process::Child),
+    /// An upload running on a background thread.
+    Thread(std::thread::JoinHandle<anyhow::Result<()>>),
+    /// The backend completed (or failed to even start) the upload
+    /// synchronously.
+    Done(anyhow::Result<()>),
+}
+
+/// Storage that compressed self-profile archives are uploaded to. The
+/// prefix/key layout and the compression are identical across backends; only
+/// the way the file is transferred differs.
+trait UploadBackend {
+    /// Starts the upload of `file` to `key` within the backend's bucket (or
+    /// directory). The upload proceeds in the background, so that several
+    /// can be in flight at once; `SelfProfileUpload::wait` collects the
+    /// result.
+    fn start_upload(&self, file: &Path, key: &str) -> UploadInFlight;
+}
+
+/// Uploads to the `rustc-perf` S3 bucket through `aws-sdk-s3` (the default).
+/// Credentials and region are resolved from the environment the same way the
+/// AWS CLI resolves them. Each upload runs on its own background thread with
+/// a small single-threaded runtime, keeping the synchronous in-flight
+/// queueing and retry handling shared with the subprocess backends.
+struct S3Backend;
+
+impl UploadBackend for S3Backend {
+    fn start_upload(&self, file: &Path, key: &str) -> UploadInFlight {
+        let file = file.to_path_buf();
+        let key = key.to_string();
+        UploadInFlight::Thread(std::thread::spawn(move || -> anyhow::Result<()> {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .context("build upload runtime")?;
+            rt.block_on(async move {
+                let config =
+                    aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                let client = aws_sdk_s3::Client::new(&config);
+                let body = aws_sdk_s3::primitives::ByteStream::from_path(&file)
+                    .await
+                    .context("read self-profile archive")?;
+                client
+                    .put_object()
+                    .bucket("rustc-perf")
+                    .key(&key)
+                    .storage_class(aws_sdk_s3::types::StorageClass::IntelligentTiering)
+                    .body(body)
+                    .send()
+                    .await
+                    .with_context(|| format!("upload s3://rustc-perf/{key}"))?;
+                anyhow::Ok(())
+            })
+        }))
+    }
+}
+
+/// Uploads through `gsutil cp` to the `rustc-perf` GCS bucket.
+struct GcsBackend;
+
+impl UploadBackend for GcsBackend {
+    fn start_upload(&self, file: &Path, key: &str) -> UploadInFlight {
+        match Command::new("gsutil")
+            .arg("-q")
+            .arg("cp")
+            .arg(file)
+            .arg(&format!("gs://rustc-perf/{key}"))
+            .spawn()
+        {
+            Ok(child) => UploadInFlight::Child(child),
+            Err(error) => {
+                UploadInFlight::Done(Err(anyhow::Error::new(error).context("spawn gsutil")))
+            }
+        }
+    }
+}
+
+/// Copies the archives into a local directory, using the same key layout as
+/// the cloud backends. Useful for inspecting self-profile data without any
+/// cloud credentials, and for testing the upload path.
+struct FsBackend {
+    root: PathBuf,
+}
+
+impl UploadBackend for FsBackend {
+    fn start_upload(&self, file: &Path, key: &str) -> UploadInFlight {
+        let target = self.root.join(key);
+        let result = (|| {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).context("create upload directory")?;
+            }
+            std::fs::copy(file, &target).context("copy self-profile archive")?;
+            Ok(())
+        })();
+        UploadInFlight::Done(result)
+    }
+}
+
+/// Whether self-profile archives should be uploaded at all. Setting
+/// `RUSTC_PERF_UPLOAD_BACKEND` is enough to enable uploads through the
+/// selected backend; `RUSTC_PERF_UPLOAD_TO_S3` is the legacy switch for the
+/// default S3 backend, kept for backwards compatibility.
+fn upload_enabled() -> bool {
+    env::var_os("RUSTC_PERF_UPLOAD_BACKEND").is_some()
+        || env::var_os("RUSTC_PERF_UPLOAD_TO_S3").is_some()
+}
+
+/// Selects the upload backend from the `RUSTC_PERF_UPLOAD_BACKEND`
+/// environment variable: `s3` (the default), `gcs`, or `fs` (which requires
+/// the target directory in `RUSTC_PERF_UPLOAD_DIR`).
+fn upload_backend() -> Box<dyn UploadBackend> {
+    match env::var("RUSTC_PERF_UPLOAD_BACKEND").as_deref() {
+        Ok("gcs") => Box::new(GcsBackend),
+        Ok("fs") => {
+            let root = env::var("RUSTC_PERF_UPLOAD_DIR")
+                .expect("RUSTC_PERF_UPLOAD_BACKEND=fs requires RUSTC_PERF_UPLOAD_DIR");
+            Box::new(FsBackend {
+                root: PathBuf::from(root),
+            })
+        }
+        Ok("s3") | Err(_) => Box::new(S3Backend),
+        Ok(other) => panic!(
+            "unknown RUSTC_PERF_UPLOAD_BACKEND value `{other}` (expected `s3`, `gcs` or `fs`)"
+        ),
+    }
+}
+
+/// Compression codec applied to self-profile archives before upload.
+enum ProfileCompression {
+    /// Fast, moderate ratio; the historical default, kept for backwards
+    /// compatibility with downstream tooling that expects `.sz` files.
+    Snappy,
+    /// Substantially better ratios for profile data, at some CPU cost.
+    /// Compresses by shelling out to the `zstd` executable.
+    Zstd,
+}
+
+/// Selects the compression codec from the `RUSTC_PERF_PROFILE_COMPRESSION`
+/// environment variable: `snappy` (the default) or `zstd`. The file
+/// extension of the uploaded archive follows the codec, so downstream
+/// tooling can tell them apart.
+fn profile_compression() -> ProfileCompression {
+    match env::var("RUSTC_PERF_PROFILE_COMPRESSION").as_deref() {
+        Ok("zstd") => ProfileCompression::Zstd,
+        Ok("snappy") | Err(_) => ProfileCompression::Snappy,
+        Ok(other) => panic!(
+            "unknown RUSTC_PERF_PROFILE_COMPRESSION value `{other}` \
+             (expected `snappy` or `zstd`)"
+        ),
+    }
+}
+
+/// Total number of attempts made for each self-profile upload, configurable
+/// via the `RUSTC_PERF_UPLOAD_RETRIES` environment variable (default 5).
+fn upload_attempts() -> u32 {
+    env::var("RUSTC_PERF_UPLOAD_RETRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5)
+        .max(1)
+}
+
+/// Uploads self-profile results to the configured storage backend
+struct SelfProfileUpload {
+    in_flight: UploadInFlight,
+    /// Keeps the compressed archive alive for retries (and deletes it when
+    /// the upload is done).
+    file: tempfile::NamedTempFile,
+    key: String,
+}
+
+impl SelfProfileUpload {
     fn new(
         prefix: PathBuf,
         collection: database::CollectionId,
         files: SelfProfileFiles,
-    ) -> SelfProfileS3Upload {
+    ) -> SelfProfileUpload {
         // Files are placed at
         //  * self-profile/<artifact id>/<benchmark>/<profile>/<scenario>
         //    /self-profile-<collection-id>.{extension}
@@ -291,41 +1147,124 @@ impl SelfProfileS3Upload {
             .context("create temporary file")
             .unwrap();
         let filename = match files {
-            SelfProfileFiles::Eight { file } => {
-                let data = std::fs::read(file).expect("read profile data");
-                let mut data = snap::read::FrameEncoder::new(&data[..]);
-                let mut compressed = Vec::new();
-                data.read_to_end(&mut compressed).expect("compressed");
-                std::fs::write(upload.path(), &compressed).expect("write compressed profile data");
-
-                format!("self-profile-{}.mm_profdata.sz", collection)
-            }
+            SelfProfileFiles::Eight { file } => match profile_compression() {
+                ProfileCompression::Snappy => {
+                    let data = std::fs::read(file).expect("read profile data");
+                    let mut data = snap::read::FrameEncoder::new(&data[..]);
+                    let mut compressed = Vec::new();
+                    data.read_to_end(&mut compressed).expect("compressed");
+                    std::fs::write(upload.path(), &compressed)
+                        .expect("write compressed profile data");
+
+                    format!("self-profile-{}.mm_profdata.sz", collection)
+                }
+                ProfileCompression::Zstd => {
+                    let status = Command::new("zstd")
+                        .arg("-q")
+                        .arg("-f")
+                        .arg(&file)
+                        .arg("-o")
+                        .arg(upload.path())
+                        .status()
+                        .expect("spawn zstd");
+                    assert!(status.success(), "zstd failed: {:?}", status);
+
+                    format!("self-profile-{}.mm_profdata.zst", collection)
+                }
+            },
         };
 
-        let child = Command::new("aws")
-            .arg("s3")
-            .arg("cp")
-            .arg("--storage-class")
-            .arg("INTELLIGENT_TIERING")
-            .arg("--only-show-errors")
-            .arg(upload.path())
-            .arg(&format!(
-                "s3://rustc-perf/{}",
-                &prefix.join(filename).to_str().unwrap()
-            ))
-            .spawn()
-            .expect("spawn aws");
+        let key = prefix.join(filename).to_str().unwrap().to_string();
+        let in_flight = upload_backend().start_upload(upload.path(), &key);
 
-        SelfProfileS3Upload(child, upload)
+        SelfProfileUpload {
+            in_flight,
+            file: upload,
+            key,
+        }
     }
 
+    /// Waits for the upload to finish, retrying transient failures with
+    /// exponential backoff. Self-profile data is auxiliary, so after the
+    /// final attempt fails the failure is logged and the collection
+    /// continues instead of aborting a multi-hour run.
     fn wait(mut self) {
         let start = std::time::Instant::now();
-        let status = self.0.wait().expect("waiting for child");
-        if !status.success() {
-            panic!("S3 upload failed: {:?}", status);
+        let attempts = upload_attempts();
+        let mut attempt = 1;
+        let mut in_flight = std::mem::replace(&mut self.in_flight, UploadInFlight::Done(Ok(())));
+        loop {
+            let result = match in_flight {
+                // A synchronous backend already completed (or failed) the
+                // upload.
+                UploadInFlight::Done(result) => result,
+                UploadInFlight::Child(mut child) => match child.wait() {
+                    Ok(status) if status.success() => Ok(()),
+                    Ok(status) => Err(anyhow::anyhow!("upload process exited with {status}")),
+                    Err(error) => Err(anyhow::Error::new(error).context("wait for upload process")),
+                },
+                UploadInFlight::Thread(handle) => match handle.join() {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::anyhow!("upload thread panicked")),
+                },
+            };
+            match result {
+                Ok(()) => {
+                    log::trace!("upload finished, additional wait: {:?}", start.elapsed());
+                    return;
+                }
+                Err(error) => {
+                    if attempt >= attempts {
+                        log::error!(
+                            "self-profile upload of {} failed after {} attempt(s) ({:#}); \
+                             continuing without it",
+                            self.key,
+                            attempt,
+                            error
+                        );
+                        return;
+                    }
+                    let delay = std::time::Duration::from_secs(2u64.pow(attempt.min(6)));
+                    log::warn!(
+                        "self-profile upload of {} failed ({:#}); retrying in {:?}",
+                        self.key,
+                        error,
+                        delay
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                    in_flight = upload_backend().start_upload(self.file.path(), &self.key);
+                }
+            }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatAggregation;
+
+    #[test]
+    fn aggregate_min() {
+        let mut values = [3.0, 1.0, 2.0];
+        assert_eq!(StatAggregation::Min.aggregate(&mut values), 1.0);
+    }
+
+    #[test]
+    fn aggregate_median_odd() {
+        let mut values = [5.0, 1.0, 3.0];
+        assert_eq!(StatAggregation::Median.aggregate(&mut values), 3.0);
+    }
+
+    #[test]
+    fn aggregate_median_even() {
+        let mut values = [4.0, 1.0, 3.0, 2.0];
+        assert_eq!(StatAggregation::Median.aggregate(&mut values), 2.5);
+    }
 
-        log::trace!("uploaded to S3, additional wait: {:?}", start.elapsed());
+    #[test]
+    fn aggregate_mean() {
+        let mut values = [1.0, 2.0, 6.0];
+        assert_eq!(StatAggregation::Mean.aggregate(&mut values), 3.0);
     }
 }
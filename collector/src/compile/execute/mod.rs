@@ -20,11 +20,16 @@ use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::process::{self, Command};
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
 
 pub mod bencher;
 mod etw_parser;
 pub mod profiler;
 mod rustc;
+pub mod site_json;
+pub mod stat_transform;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PerfTool {
@@ -51,6 +56,7 @@ impl PerfTool {
             | BenchTool(PerfStatSelfProfile)
             | BenchTool(XperfStat)
             | BenchTool(XperfStatSelfProfile)
+            | BenchTool(TimeStat)
             | ProfileTool(SelfProfile)
             | ProfileTool(PerfRecord)
             | ProfileTool(Oprofile)
@@ -61,6 +67,7 @@ impl PerfTool {
             | ProfileTool(DhatCopy)
             | ProfileTool(Massif)
             | ProfileTool(Bytehound)
+            | ProfileTool(Heaptrack)
             | ProfileTool(Eprintln)
             | ProfileTool(DepGraph)
             | ProfileTool(MonoItems)
@@ -72,12 +79,44 @@ impl PerfTool {
                 }
             }
             ProfileTool(LlvmLines) => match profile {
-                Profile::Debug | Profile::Opt => Some("llvm-lines"),
+                Profile::Debug | Profile::Opt | Profile::OptDebuginfo => Some("llvm-lines"),
                 Profile::Check | Profile::Doc | Profile::Clippy => None,
             },
         }
     }
 
+    /// The effective `--emit` set of the final rustc invocation for this perf
+    /// tool and profile. The collector never passes `--emit` explicitly (apart
+    /// from the `LlvmIr` profiler, which does so in `rustc-fake`), so this
+    /// spells out what cargo ends up requesting for each profile. It is stored
+    /// as collection metadata so that results are interpretable: e.g. Check
+    /// only emits metadata while Debug also links, which explains structural
+    /// differences between their timings.
+    fn emit_types(&self, profile: Profile) -> &'static str {
+        if let PerfTool::ProfileTool(profiler::Profiler::LlvmIr) = self {
+            return "llvm-ir";
+        }
+        match profile {
+            // `cargo check` and `cargo clippy` only request metadata.
+            Profile::Check | Profile::Clippy => "dep-info,metadata",
+            Profile::Debug | Profile::Opt | Profile::OptDebuginfo => "dep-info,metadata,link",
+            // Rustdoc does not go through `--emit` at all; record the
+            // documentation output for completeness.
+            Profile::Doc => "doc",
+        }
+    }
+
+    /// Checks that the external binary this perf tool needs is installed.
+    /// The bench tools are already validated in `BenchProcessor::new`; this
+    /// covers the profilers, whose tools would otherwise only fail deep
+    /// inside `rustc-fake` after the benchmark has been prepared.
+    pub fn check_available(&self) -> anyhow::Result<()> {
+        match self {
+            PerfTool::BenchTool(_) => Ok(()),
+            PerfTool::ProfileTool(profiler) => profiler.check_available(),
+        }
+    }
+
     /// Should return true if this perf tool calls Cargo "recursively" inside of it.
     /// This is not compatible with a check that is performed to make sure that only the
     /// final rustc is invoked during a benchmark/profiling phase.
@@ -95,6 +134,7 @@ impl PerfTool {
             | BenchTool(PerfStatSelfProfile)
             | BenchTool(XperfStat)
             | BenchTool(XperfStatSelfProfile)
+            | BenchTool(TimeStat)
             | ProfileTool(SelfProfile)
             | ProfileTool(PerfRecord)
             | ProfileTool(Oprofile)
@@ -105,6 +145,7 @@ impl PerfTool {
             | ProfileTool(DhatCopy)
             | ProfileTool(Massif)
             | ProfileTool(Bytehound)
+            | ProfileTool(Heaptrack)
             | ProfileTool(MonoItems)
             | ProfileTool(LlvmIr)
             | ProfileTool(Eprintln) => true,
@@ -115,6 +156,351 @@ impl PerfTool {
     }
 }
 
+/// A global budget on the number of measured rustc invocations, used for fast
+/// smoke tests ("does collection even work?") where the interesting signal is
+/// that some measurements succeed, not that the whole suite runs. Unlimited by
+/// default.
+static COMPILER_INVOCATION_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// The number of measured rustc invocations performed so far.
+static COMPILER_INVOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_compiler_invocation_limit(limit: usize) {
+    COMPILER_INVOCATION_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+/// The number of measured rustc invocations performed so far.
+pub fn compiler_invocation_count() -> usize {
+    COMPILER_INVOCATION_COUNT.load(Ordering::Relaxed)
+}
+
+/// Returns true once the configured invocation budget has been used up.
+pub fn compiler_invocation_budget_exhausted() -> bool {
+    COMPILER_INVOCATION_COUNT.load(Ordering::Relaxed)
+        >= COMPILER_INVOCATION_LIMIT.load(Ordering::Relaxed)
+}
+
+/// Checks that the given Cargo executable supports the unstable `--keep-going`
+/// flag (enabled with the `CARGO_KEEP_GOING` environment variable).
+/// This should be executed before starting a benchmark suite, to avoid failing
+/// on every cargo invocation in the middle of the run.
+pub fn check_keep_going_supported(cargo: &Path) -> anyhow::Result<()> {
+    let mut cmd = Command::new(cargo);
+    // `--keep-going` is unstable, so it needs `-Zunstable-options`, which in
+    // turn needs a nightly cargo (or RUSTC_BOOTSTRAP).
+    cmd.env("RUSTC_BOOTSTRAP", "1")
+        .arg("check")
+        .arg("-Zunstable-options")
+        .arg("--keep-going")
+        .arg("--help");
+    command_output(&mut cmd)
+        .map(|_| ())
+        .map_err(|error| anyhow::anyhow!("cargo does not support `--keep-going`: {:?}", error))
+}
+
+/// The perf counters gathered for every measured rustc invocation (this list
+/// must stay in sync with the `perf stat` invocation in `rustc-fake`).
+#[cfg(unix)]
+const PERF_COUNTERS: &str =
+    "instructions:u,cycles:u,task-clock,cpu-clock,faults,context-switches,branch-misses,cache-misses";
+
+/// The full `perf stat` event list: `PERF_COUNTERS`, extended with the
+/// comma-separated events of the `RUSTC_PERF_EXTRA_PERF_EVENTS` environment
+/// variable (e.g. `cache-references,branch-instructions`). `rustc-fake`
+/// builds the same list, so the extra events end up as regular stats. Note
+/// that requesting more events than the PMU has slots makes the kernel
+/// multiplex them, which reduces the accuracy of the scaled values.
+#[cfg(unix)]
+fn perf_event_list() -> String {
+    let mut events = PERF_COUNTERS.to_string();
+    if let Ok(extra) = env::var("RUSTC_PERF_EXTRA_PERF_EVENTS") {
+        if !extra.is_empty() {
+            events.push(',');
+            events.push_str(&extra);
+        }
+    }
+    events
+}
+
+/// Checks that `perf stat` can actually collect every counter the run intends
+/// to gather, by measuring a trivial command. This should be executed before
+/// starting a benchmark suite: on a new collector machine, discovering that
+/// e.g. `instructions:u` is unavailable (restrictive `perf_event_paranoid`
+/// settings, virtualized hardware) only hours into a run is painful.
+#[cfg(unix)]
+pub fn check_perf_counters_supported() -> anyhow::Result<()> {
+    let events = perf_event_list();
+    let mut cmd = Command::new("perf");
+    cmd.arg("stat")
+        .arg("-x;")
+        .arg("-e")
+        .arg(&events)
+        .arg("true");
+    let output = command_output(&mut cmd)
+        .map_err(|error| anyhow::anyhow!("cannot run `perf stat`: {:?}", error))?;
+
+    // perf writes the statistics to stderr, in the same `cnt;unit;name;...`
+    // format parsed by `process_stat_output`.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut reported = Vec::new();
+    let mut unavailable = Vec::new();
+    for line in stderr.lines() {
+        let mut parts = line.split(';').map(|s| s.trim());
+        let Some(cnt) = parts.next() else {
+            continue;
+        };
+        let Some(name) = parts.nth(1) else {
+            continue;
+        };
+        reported.push(name.to_string());
+        if cnt == "<not supported>" || cnt == "<not counted>" || cnt.is_empty() {
+            unavailable.push(name.to_string());
+        }
+    }
+    for counter in events.split(',') {
+        if !reported.iter().any(|name| name == counter) {
+            unavailable.push(counter.to_string());
+        }
+    }
+
+    if unavailable.is_empty() {
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "the perf counter(s) {} are not available on this machine; \
+             access to hardware counters can be restricted by the kernel \
+             (try `sudo sysctl kernel.perf_event_paranoid=-1`) or simply \
+             unavailable on virtualized hardware",
+            unavailable.join(", ")
+        )
+    }
+}
+
+/// Checks that `/proc/sys/kernel/perf_event_paranoid` permits unprivileged
+/// access to the hardware counters, and fails with remediation guidance
+/// otherwise. Unlike [`check_perf_counters_supported`] this does not need to
+/// run `perf`, so it is cheap enough to run before every collection: a too
+/// restrictive setting (above 2, e.g. the Debian default of 3) makes `perf
+/// stat` silently report `<not supported>` for every hardware counter, and
+/// the whole collection would record nothing.
+#[cfg(target_os = "linux")]
+pub fn check_perf_event_paranoid() -> anyhow::Result<()> {
+    let path = "/proc/sys/kernel/perf_event_paranoid";
+    // If the file is missing or unreadable the kernel was built without perf
+    // event support; let the `perf stat` invocation itself produce the error.
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Ok(());
+    };
+    let Ok(level) = contents.trim().parse::<i32>() else {
+        return Ok(());
+    };
+    if level > 2 {
+        anyhow::bail!(
+            "{path} is {level}, which disables unprivileged access to the \
+             performance counters, so `perf stat` would report `<not supported>` \
+             for every hardware counter; set perf_event_paranoid <= 1 \
+             (e.g. `sudo sysctl kernel.perf_event_paranoid=1`) before collecting"
+        );
+    }
+    Ok(())
+}
+
+/// The target triple to cross-compile the benchmarks for, from the
+/// `RUSTC_PERF_TARGET` environment variable (e.g. `wasm32-unknown-unknown`).
+/// `None` means the host target. Results measured for a cross target are
+/// recorded under a benchmark name tagged with the triple, so they never
+/// collide with host measurements.
+pub fn cross_target() -> Option<String> {
+    env::var("RUSTC_PERF_TARGET")
+        .ok()
+        .filter(|target| !target.is_empty())
+}
+
+/// Renders a fully-constructed `Command` the way a shell user would run it:
+/// environment overrides first, then the program and its arguments. Used by
+/// the dry-run mode.
+fn format_command(cmd: &Command) -> String {
+    use std::fmt::Write;
+    let mut out = String::new();
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => {
+                let _ = write!(out, "{}={:?} ", key.to_string_lossy(), value);
+            }
+            // An explicitly removed variable.
+            None => {
+                let _ = write!(out, "{}= ", key.to_string_lossy());
+            }
+        }
+    }
+    let _ = write!(out, "{:?}", cmd.get_program());
+    for arg in cmd.get_args() {
+        let _ = write!(out, " {:?}", arg);
+    }
+    out
+}
+
+/// Name of the file (inside the benchmark's working directory) into which the
+/// `rustc-fake` and `build-script-fake` shims append `compile:<secs>` /
+/// `run:<secs>` entries whenever a build script is compiled or executed, when
+/// `RUSTC_PERF_BUILD_SCRIPT_TIMES` is enabled.
+const BUILD_SCRIPT_TIMES_FILENAME: &str = ".build-script-times";
+
+/// Folds the build-script timings accumulated by the shims into the output of
+/// the measured cargo invocation as `!build-script-compile:` /
+/// `!build-script-run:` marker lines, and removes the file. Build scripts
+/// normally compile and run during preparation, so their cost is attributed
+/// to the first measured iteration that follows; iterations without
+/// build-script activity simply record no such stat.
+fn append_build_script_times(output: &mut process::Output, cwd: &Path) {
+    let file = cwd.join(BUILD_SCRIPT_TIMES_FILENAME);
+    let Ok(data) = fs::read_to_string(&file) else {
+        return;
+    };
+    let _ = fs::remove_file(&file);
+
+    let mut compile = 0.0;
+    let mut run = 0.0;
+    for line in data.lines() {
+        let Some((kind, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Ok(value) = value.parse::<f64>() else {
+            continue;
+        };
+        match kind {
+            "compile" => compile += value,
+            "run" => run += value,
+            _ => {}
+        }
+    }
+
+    if output.stdout.last().is_some_and(|byte| *byte != b'\n') {
+        output.stdout.push(b'\n');
+    }
+    if compile > 0.0 {
+        output
+            .stdout
+            .extend_from_slice(format!("!build-script-compile:{}\n", compile).as_bytes());
+    }
+    if run > 0.0 {
+        output
+            .stdout
+            .extend_from_slice(format!("!build-script-run:{}\n", run).as_bytes());
+    }
+}
+
+/// Error returned when a benchmark exceeded the `timeout` configured in its
+/// perf-config.json. Distinct from ordinary build failures, so that callers
+/// can record it and move on (and so it is never mistaken for a build
+/// incompatibility).
+#[derive(Debug, thiserror::Error)]
+#[error("benchmark timed out after {timeout:?}; killed the cargo process tree")]
+pub struct BenchmarkTimeout {
+    pub timeout: Duration,
+}
+
+/// Runs the command with a hard wall-clock limit. On expiry, the whole
+/// process group is killed (on Unix): cargo spawns rustc, so killing only
+/// the direct child would leave a runaway compilation behind. The command
+/// must have been placed in its own process group beforehand.
+async fn run_with_benchmark_timeout(
+    mut cmd: tokio::process::Command,
+    timeout: Duration,
+) -> anyhow::Result<process::Output> {
+    // Ensure the direct child does not outlive a timed-out future on
+    // platforms without process groups.
+    cmd.kill_on_drop(true);
+    let child = cmd
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to spawn process for cmd: {:?}", cmd))?;
+    #[cfg(unix)]
+    let pid = child.id();
+    match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(output) => {
+            let output = output?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!(
+                    "expected success, got {}\n\nstderr={}\n\n stdout={}\n",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr),
+                    String::from_utf8_lossy(&output.stdout)
+                ));
+            }
+            Ok(output)
+        }
+        Err(_) => {
+            #[cfg(unix)]
+            if let Some(pid) = pid {
+                // The child is the process group leader (`process_group(0)`),
+                // so a negative pid kills the whole group.
+                unsafe {
+                    libc::kill(-(pid as i32), libc::SIGKILL);
+                }
+            }
+            Err(BenchmarkTimeout { timeout }.into())
+        }
+    }
+}
+
+/// Touches every file selected by `pattern`, which is either a literal path
+/// or a simple glob (`*` and `?` within one path segment, `**/` for zero or
+/// more whole directories), resolved relative to `cwd`.
+fn touch_pattern(cwd: &Path, pattern: &str) -> anyhow::Result<()> {
+    if !pattern.contains(['*', '?']) {
+        return utils::fs::touch(&cwd.join(Path::new(pattern)));
+    }
+    let regex = glob_to_regex(pattern)?;
+    let mut matched = false;
+    for entry in walkdir::WalkDir::new(cwd) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(cwd) else {
+            continue;
+        };
+        let Some(relative) = relative.to_str() else {
+            continue;
+        };
+        if regex.is_match(relative) {
+            utils::fs::touch(entry.path())?;
+            matched = true;
+        }
+    }
+    if !matched {
+        log::warn!("touch_file pattern `{pattern}` matched no files");
+    }
+    Ok(())
+}
+
+/// Translates a `touch_file` glob into an anchored regex over `/`-separated
+/// relative paths.
+fn glob_to_regex(pattern: &str) -> anyhow::Result<regex::Regex> {
+    let mut translated = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    translated.push_str("(?:[^/]+/)*");
+                } else {
+                    translated.push_str(".*");
+                }
+            }
+            '*' => translated.push_str("[^/]*"),
+            '?' => translated.push_str("[^/]"),
+            c => translated.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    translated.push('$');
+    regex::Regex::new(&translated)
+        .with_context(|| format!("invalid touch_file pattern `{pattern}`"))
+}
+
 pub struct CargoProcess<'a> {
     pub toolchain: &'a Toolchain,
     pub cwd: &'a Path,
@@ -126,8 +512,37 @@ pub struct CargoProcess<'a> {
     pub manifest_path: String,
     pub cargo_args: Vec<String>,
     pub rustc_args: Vec<String>,
-    pub touch_file: Option<String>,
+    /// Flags applied to every rustc invocation through `RUSTFLAGS`, unlike
+    /// `rustc_args` which only reaches the leaf crate.
+    pub rustflags: Option<String>,
+    /// Paths (and simple globs) whose modification time is reset before an
+    /// incremental build, instead of touching everything under the manifest
+    /// directory.
+    pub touch_file: Option<Vec<String>>,
+    /// Benchmark-specific environment variables from perf-config.json.
+    pub env: HashMap<String, String>,
+    /// Hard wall-clock limit for every cargo invocation of this benchmark;
+    /// on expiry the whole cargo process tree is killed.
+    pub timeout: Option<Duration>,
+    /// Print every fully-constructed cargo invocation (including environment
+    /// overrides) instead of executing it, for debugging a benchmark's
+    /// perf-config.json without compiling anything.
+    pub dry_run: bool,
+    /// Cross-compile for this target triple (`--target`) instead of the host.
+    /// Build scripts and proc macros still compile for the host, but only the
+    /// leaf crate is wrapped by `rustc-fake` (through `cargo rustc -- ...`),
+    /// so the profiled invocation is always the cross-compiled one.
+    pub target: Option<String>,
     pub jobserver: Option<jobserver::Client>,
+    /// The workspace member to compile (and wrap) instead of the package that
+    /// `cargo pkgid` resolves in the benchmark directory. Used when measuring
+    /// individual members of a workspace benchmark.
+    pub package: Option<String>,
+    /// Cached `-p` spec of the benchmark package, shared across all cargo
+    /// invocations of one benchmark so that `cargo pkgid` runs once per
+    /// benchmark instead of once per profile/scenario/iteration (see
+    /// `get_pkgid`).
+    pub pkgid_cache: &'a OnceLock<String>,
 }
 
 impl<'a> CargoProcess<'a> {
@@ -149,6 +564,11 @@ impl<'a> CargoProcess<'a> {
 
     fn base_command(&self, cwd: &Path, subcommand: &str) -> Command {
         let mut cmd = Command::new(Path::new(&self.toolchain.components.cargo));
+        // Benchmark-specific environment variables are applied first, so that
+        // the variables the collector controls below always take precedence.
+        for (key, value) in &self.env {
+            cmd.env(key, value);
+        }
         cmd
             // Not all cargo invocations (e.g. `cargo clean`) need all of these
             // env vars set, but it doesn't hurt to have them.
@@ -181,19 +601,79 @@ impl<'a> CargoProcess<'a> {
             cmd.env("CLIPPY", &*FAKE_CLIPPY).env("CLIPPY_REAL", c);
         }
 
+        // Cargo appends these to the arguments of every rustc (i.e. the
+        // `rustc-fake` shim) invocation, which passes them through to the
+        // real rustc.
+        if let Some(flags) = &self.rustflags {
+            cmd.env("RUSTFLAGS", flags);
+        }
+
+        if let Some(target) = &self.target {
+            cmd.arg("--target").arg(target);
+        }
+
+        // Opt-in separate timing of build scripts: the `rustc-fake` and
+        // `build-script-fake` shims append compile/run entries to this file,
+        // which `run_rustc` turns into `build-script-compile` /
+        // `build-script-run` stats after the measured invocation.
+        if env::var_os("RUSTC_PERF_BUILD_SCRIPT_TIMES").is_some() {
+            cmd.env(
+                "BUILD_SCRIPT_TIMES_FILE",
+                self.cwd.join(BUILD_SCRIPT_TIMES_FILENAME),
+            );
+        }
+
         for config in &self.toolchain.components.cargo_configs {
             cmd.arg("--config").arg(config);
         }
+
+        // On sandboxed/air-gapped collectors, cargo must not try to access the
+        // network: build entirely from vendored or cached dependencies.
+        // If a dependency is missing from the cache, cargo itself reports that
+        // it cannot be fetched in offline mode.
+        if env::var_os("CARGO_OFFLINE").is_some() {
+            cmd.arg("--offline");
+        }
+        // Optionally redirect crates.io to a vendored sources directory
+        // (as produced by `cargo vendor`).
+        if let Ok(vendor_dir) = env::var("CARGO_VENDOR_DIR") {
+            cmd.arg("--config")
+                .arg("source.crates-io.replace-with='vendored-sources'");
+            cmd.arg("--config")
+                .arg(format!("source.vendored-sources.directory='{vendor_dir}'"));
+        }
         cmd
     }
 
+    /// Returns the `-p` spec of the benchmark package.
+    ///
+    /// The raw `cargo pkgid` output embeds the absolute path of the build
+    /// directory, which differs between iterations, so it is reduced to the
+    /// path-independent `name@version` form. That form is cached across the
+    /// cargo invocations of one benchmark, because spawning `cargo pkgid`
+    /// once per profile/scenario/iteration adds up for benchmarks with slow
+    /// metadata resolution.
     fn get_pkgid(&self, cwd: &Path) -> anyhow::Result<String> {
+        if let Some(cached) = self.pkgid_cache.get() {
+            return Ok(cached.clone());
+        }
         let mut pkgid_cmd = self.base_command(cwd, "pkgid");
         let out = command_output(&mut pkgid_cmd)
             .with_context(|| format!("failed to obtain pkgid in '{:?}'", cwd))?
             .stdout;
-        let package_id = str::from_utf8(&out).unwrap();
-        Ok(package_id.trim().to_string())
+        let package_id = str::from_utf8(&out).unwrap().trim();
+        let spec = match package_id.rsplit_once('#') {
+            // `path+file:///.../hello#hello-world@0.1.0`
+            Some((_, fragment)) if fragment.contains('@') => fragment.to_string(),
+            // `path+file:///.../hello-world#0.1.0`: the package is named
+            // after the final path segment.
+            Some((path, version)) => {
+                let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or(path);
+                format!("{name}@{version}")
+            }
+            None => package_id.to_string(),
+        };
+        Ok(self.pkgid_cache.get_or_init(|| spec).clone())
     }
 
     pub fn jobserver(mut self, server: jobserver::Client) -> Self {
@@ -252,7 +732,14 @@ impl<'a> CargoProcess<'a> {
                 };
 
             let mut cmd = self.base_command(self.cwd, cargo_subcommand);
-            cmd.arg("-p").arg(self.get_pkgid(self.cwd)?);
+            match &self.package {
+                Some(member) => {
+                    cmd.arg("-p").arg(member);
+                }
+                None => {
+                    cmd.arg("-p").arg(self.get_pkgid(self.cwd)?);
+                }
+            }
             match self.profile {
                 Profile::Check => {
                     cmd.arg("--profile").arg("check");
@@ -260,7 +747,7 @@ impl<'a> CargoProcess<'a> {
                 Profile::Debug => {}
                 Profile::Doc => {}
                 Profile::Clippy => {}
-                Profile::Opt => {
+                Profile::Opt | Profile::OptDebuginfo => {
                     cmd.arg("--release");
                 }
             }
@@ -269,13 +756,24 @@ impl<'a> CargoProcess<'a> {
                 cmd.arg("-Zunstable-options");
                 cmd.arg("-Ztimings");
             }
+            // `--keep-going` changes how cargo schedules work (and continues
+            // past errors), which mainly affects multi-target/workspace
+            // benchmarks. It is still unstable, so it has to be enabled
+            // together with `-Zunstable-options`.
+            if env::var_os("CARGO_KEEP_GOING").is_some() {
+                cmd.arg("-Zunstable-options");
+                cmd.arg("--keep-going");
+            }
             cmd.arg("--");
 
-            match self.backend {
-                CodegenBackend::Llvm => {}
-                CodegenBackend::Cranelift => {
-                    cmd.arg("-Zcodegen-backend=cranelift");
-                }
+            if let Some(flag) = self.backend.rustc_flag() {
+                cmd.arg(flag);
+            }
+
+            // `cargo rustc` forwards post-`--` arguments only to the leaf
+            // crate, which is exactly the crate being measured.
+            if self.profile == Profile::OptDebuginfo {
+                cmd.arg("-Cdebuginfo=1");
             }
 
             // --wrap-rustc-with is not a valid rustc flag. But rustc-fake
@@ -285,6 +783,20 @@ impl<'a> CargoProcess<'a> {
             // onto rustc for the final crate, which is exactly the crate for which
             // we want to wrap rustc.
             if needs_final {
+                // Enforce the global invocation budget (see
+                // `set_compiler_invocation_limit`). Only measured invocations
+                // count towards it: dependency builds are preparation, not
+                // work we are trying to bound.
+                if compiler_invocation_budget_exhausted() {
+                    log::info!(
+                        "compiler invocation limit reached; skipping a measured rustc \
+                         invocation for {}",
+                        self.processor_name
+                    );
+                    return Ok(());
+                }
+                COMPILER_INVOCATION_COUNT.fetch_add(1, Ordering::Relaxed);
+
                 let processor = self
                     .processor_etc
                     .as_mut()
@@ -300,6 +812,12 @@ impl<'a> CargoProcess<'a> {
                 cmd.arg("--wrap-rustc-with");
                 cmd.arg(perf_tool_name);
                 cmd.args(&self.rustc_args);
+                // Optionally gather per-pass timings of the measured (leaf)
+                // rustc invocation; parsed from stderr by
+                // `process_stat_output`.
+                if env::var_os("RUSTC_PERF_TIME_PASSES").is_some() {
+                    cmd.arg("-Ztime-passes");
+                }
 
                 // If we're not going to be in a processor, then there's no
                 // point ensuring that we recompile anything -- that just wastes
@@ -309,8 +827,10 @@ impl<'a> CargoProcess<'a> {
                 // benchmarking, so as to not refresh dependencies, which may be
                 // in-tree (e.g., in the case of the servo crates there are a lot of
                 // other components).
-                if let Some(file) = &self.touch_file {
-                    utils::fs::touch(&self.cwd.join(Path::new(&file)))?;
+                if let Some(patterns) = &self.touch_file {
+                    for pattern in patterns {
+                        touch_pattern(self.cwd, pattern)?;
+                    }
                 } else {
                     utils::fs::touch_all(
                         &self.cwd.join(
@@ -341,8 +861,31 @@ impl<'a> CargoProcess<'a> {
 
             log::debug!("{:?}", cmd);
 
+            if self.dry_run {
+                eprintln!("dry run: {}", format_command(&cmd));
+                return Ok(());
+            }
+
+            #[cfg(unix)]
+            if self.timeout.is_some() {
+                use std::os::unix::process::CommandExt;
+                // Place cargo in its own process group, so that a timeout can
+                // kill cargo together with all the rustc processes it
+                // spawned, not just the direct child.
+                cmd.process_group(0);
+            }
             let cmd = tokio::process::Command::from(cmd);
-            let output = async_command_output(cmd).await?;
+            let start = chrono::Utc::now();
+            let start_mono = std::time::Instant::now();
+            let mut output = match self.timeout {
+                Some(timeout) => run_with_benchmark_timeout(cmd, timeout).await?,
+                None => async_command_output(cmd).await?,
+            };
+            let duration = start_mono.elapsed();
+
+            if needs_final {
+                append_build_script_times(&mut output, self.cwd);
+            }
 
             if let Some((ref mut processor, scenario, scenario_str, patch)) = self.processor_etc {
                 let data = ProcessOutputData {
@@ -353,6 +896,8 @@ impl<'a> CargoProcess<'a> {
                     scenario_str,
                     patch,
                     backend: self.backend,
+                    start,
+                    duration,
                 };
                 match processor.process_output(&data, output).await {
                     Ok(Retry::No) => return Ok(()),
@@ -366,18 +911,47 @@ impl<'a> CargoProcess<'a> {
     }
 }
 
+/// Resolves the path of one of the fake shim binaries. The path is normally
+/// derived from the location of the collector binary itself, but it can be
+/// overridden through an environment variable for deployment layouts where
+/// the shims live elsewhere (e.g. symlinked binaries or containers where the
+/// collector is relocated separately from the shims).
+fn fake_tool_path(env_var: &str, name: &str) -> PathBuf {
+    if let Some(path) = env::var_os(env_var) {
+        let path = PathBuf::from(path);
+        if !is_executable(&path) {
+            panic!(
+                "{env_var} is set to `{}`, which does not exist or is not executable",
+                path.display()
+            );
+        }
+        return path;
+    }
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    path.push(name);
+    path
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
 lazy_static::lazy_static! {
-    static ref FAKE_RUSTC: PathBuf = {
-        let mut fake_rustc = env::current_exe().unwrap();
-        fake_rustc.pop();
-        fake_rustc.push("rustc-fake");
-        fake_rustc
-    };
+    static ref FAKE_RUSTC: PathBuf = fake_tool_path("RUSTC_PERF_FAKE_RUSTC", "rustc-fake");
     static ref FAKE_RUSTDOC: PathBuf = {
-        let mut fake_rustdoc = env::current_exe().unwrap();
-        fake_rustdoc.pop();
-        fake_rustdoc.push("rustdoc-fake");
-        // link from rustc-fake to rustdoc-fake
+        let fake_rustdoc = fake_tool_path("RUSTC_PERF_FAKE_RUSTDOC", "rustdoc-fake");
+        // link from rustc-fake to rustdoc-fake; an overridden path was already
+        // validated to exist, so no symlink is created for it
         if !fake_rustdoc.exists() {
             #[cfg(unix)]
             use std::os::unix::fs::symlink;
@@ -389,10 +963,9 @@ lazy_static::lazy_static! {
         fake_rustdoc
     };
     static ref FAKE_CLIPPY: PathBuf = {
-        let mut fake_clippy = env::current_exe().unwrap();
-        fake_clippy.pop();
-        fake_clippy.push("clippy-fake");
-        // link from rustc-fake to rustdoc-fake
+        let fake_clippy = fake_tool_path("RUSTC_PERF_FAKE_CLIPPY", "clippy-fake");
+        // link from rustc-fake to clippy-fake; an overridden path was already
+        // validated to exist, so no symlink is created for it
         if !fake_clippy.exists() {
             #[cfg(unix)]
             use std::os::unix::fs::symlink;
@@ -411,6 +984,15 @@ pub enum Retry {
     Yes,
 }
 
+/// An output artifact produced by a [`Processor`], together with the profile
+/// and scenario it belongs to.
+#[derive(Debug, Clone)]
+pub struct ProcessorArtifact {
+    pub profile: Profile,
+    pub scenario: String,
+    pub path: PathBuf,
+}
+
 pub struct ProcessOutputData<'a> {
     name: BenchmarkName,
     cwd: &'a Path,
@@ -419,6 +1001,12 @@ pub struct ProcessOutputData<'a> {
     scenario_str: &'a str,
     patch: Option<&'a Patch>,
     backend: CodegenBackend,
+    /// Wall-clock time at which the measured invocation started.
+    start: chrono::DateTime<chrono::Utc>,
+    /// Monotonic duration of the invocation. Paired with `start`, this yields
+    /// an end timestamp that stays orderable even if the system clock is
+    /// adjusted mid-run.
+    duration: std::time::Duration,
 }
 
 /// Trait used by `Benchmark::measure()` to provide different kinds of
@@ -452,13 +1040,47 @@ pub trait Processor {
     fn finished_first_collection(&mut self) -> bool {
         false
     }
+
+    /// Called just before the measured cargo invocation of the given scenario
+    /// starts, with the 0-based iteration index. Gives embedders a hook for
+    /// live progress reporting; the default implementation does nothing.
+    fn on_iteration_start(&mut self, _profile: Profile, _scenario: Scenario, _iteration: usize) {}
+
+    /// Called once the measured cargo invocation of the given scenario has
+    /// finished and its output has been processed. The counterpart of
+    /// [`Processor::on_iteration_start`].
+    fn on_iteration_complete(&mut self, _profile: Profile, _scenario: Scenario, _iteration: usize) {
+    }
+
+    /// The per-iteration values of the primary stat (`instructions:u`,
+    /// falling back to `wall-time`) of the `Full` scenario gathered in the
+    /// current collection, used to decide when an adaptive run count can
+    /// stop. Processors that do not retain per-iteration stats return an
+    /// empty slice, which disables early stopping.
+    fn iteration_stat_samples(&self) -> &[f64] {
+        &[]
+    }
+
+    /// Toggles whether results processed from now on should be thrown away
+    /// instead of recorded, used for warmup iterations. Processors that do
+    /// not record anything can ignore the hint, which is what the default
+    /// implementation does.
+    fn set_discard_results(&mut self, _discard: bool) {}
+
+    /// The output artifacts this processor has produced so far, so that
+    /// callers can locate them (e.g. to upload or open them) without
+    /// re-deriving the output directory layout. Only profiler-type processors
+    /// produce artifacts; the default implementation returns nothing.
+    fn output_artifacts(&self) -> Vec<ProcessorArtifact> {
+        Vec::new()
+    }
 }
 
 fn store_documentation_size_into_stats(stats: &mut Stats, doc_dir: &Path) {
     match utils::fs::get_file_count_and_size(doc_dir) {
         Ok((count, size)) => {
-            stats.insert("size:doc_files_count".to_string(), count as f64);
-            stats.insert("size:doc_bytes".to_string(), size as f64);
+            stats.insert_integer("size:doc_files_count".to_string(), count);
+            stats.insert_integer("size:doc_bytes".to_string(), size);
         }
         Err(error) => log::error!(
             "Cannot get size of documentation directory {}: {:?}",
@@ -468,11 +1090,153 @@ fn store_documentation_size_into_stats(stats: &mut Stats, doc_dir: &Path) {
     }
 }
 
+/// Inserts metrics derived from the hardware counters — `ipc` (instructions
+/// per cycle) and `cache-miss-rate` (cache misses per cache reference) — when
+/// their inputs are present, so that downstream consumers do not each
+/// recompute the same ratios. `cache-references` is not measured by default;
+/// it can be enabled through `RUSTC_PERF_EXTRA_PERF_EVENTS`.
+fn store_derived_stats(stats: &mut Stats) {
+    if let (Some(instructions), Some(cycles)) = (
+        stats.get("instructions:u"),
+        // `perf` reports `cycles:u`; the ETW path on Windows reports `cycles`.
+        stats.get("cycles:u").or_else(|| stats.get("cycles")),
+    ) {
+        if cycles > 0.0 {
+            stats.insert("ipc".into(), instructions / cycles);
+        }
+    }
+    if let (Some(misses), Some(references)) =
+        (stats.get("cache-misses"), stats.get("cache-references"))
+    {
+        if references > 0.0 {
+            stats.insert("cache-miss-rate".into(), misses / references);
+        }
+    }
+}
+
+/// Records the on-disk size of the `incremental-state` directory (the
+/// incremental cache passed to rustc via `-Cincremental` in `run_rustc`) as a
+/// `size:incr_cache_bytes` stat. Incremental compilation trades disk for
+/// time, so cache bloat regressions are worth tracking alongside the
+/// existing artifact sizes.
+fn store_incremental_cache_size_into_stats(stats: &mut Stats, cwd: &Path) {
+    let dir = cwd.join("incremental-state");
+    let mut total = 0u64;
+    for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
+        if let Ok(metadata) = entry.metadata() {
+            if metadata.is_file() {
+                total += metadata.len();
+            }
+        }
+    }
+    if total > 0 {
+        stats.insert_integer("size:incr_cache_bytes".into(), total);
+    }
+}
+
+/// Records the sizes of the classic object sections of the leaf crate's
+/// emitted artifact — summed across codegen units for rlibs — as `size:text`,
+/// `size:data`, `size:rodata` and `size:bss` stats (enabled via the
+/// `RUSTC_PERF_SECTION_SIZES` environment variable). The `.text` size is a
+/// much more direct signal for codegen regressions than the total artifact
+/// size, which is diluted by metadata and debuginfo.
+fn store_section_sizes_into_stats(stats: &mut Stats, cwd: &Path, profile: Profile) {
+    let deps_dir = cwd
+        .join("target")
+        .join(if matches!(profile, Profile::Opt | Profile::OptDebuginfo) {
+            "release"
+        } else {
+            "debug"
+        })
+        .join("deps");
+    // The leaf crate is compiled last, so its artifact is the most recently
+    // modified one in the deps directory.
+    let Ok(entries) = fs::read_dir(&deps_dir) else {
+        return;
+    };
+    let mut artifact: Option<(std::time::SystemTime, PathBuf)> = None;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let is_artifact = matches!(ext, "rlib" | "a" | "so" | "dylib" | "dll" | "exe")
+            || (ext.is_empty() && is_executable(&path));
+        if !is_artifact {
+            continue;
+        }
+        let Ok(modified) = entry.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+        if artifact.as_ref().map_or(true, |(time, _)| modified > *time) {
+            artifact = Some((modified, path));
+        }
+    }
+    let Some((_, path)) = artifact else {
+        return;
+    };
+    match crate::artifact_stats::ArtifactStats::from_path(&path) {
+        Ok(artifact_stats) => {
+            // Section names differ between ELF, Mach-O and PE; sum whichever
+            // spellings are present.
+            for (stat, names) in [
+                ("text", [".text", "__text"].as_slice()),
+                ("data", &[".data", "__data"]),
+                ("rodata", &[".rodata", ".rdata", "__const"]),
+                ("bss", &[".bss", "__bss"]),
+            ] {
+                let size: u64 = artifact_stats
+                    .sections
+                    .iter()
+                    .filter(|(name, _)| names.contains(&name.as_str()))
+                    .map(|(_, size)| size)
+                    .sum();
+                stats.insert_integer(format!("size:{stat}"), size);
+            }
+        }
+        Err(error) => log::error!(
+            "Cannot parse section sizes from {}: {:?}",
+            path.display(),
+            error
+        ),
+    }
+}
+
 fn store_artifact_sizes_into_stats(stats: &mut Stats, profile: &SelfProfile) {
     for artifact in profile.artifact_sizes.iter() {
-        stats
-            .stats
-            .insert(format!("size:{}", artifact.label), artifact.value as f64);
+        stats.insert_integer(format!("size:{}", artifact.label), artifact.value);
+    }
+}
+
+/// Records the overall query cache hit rate (total cache hits / total
+/// invocations, across all queries) as a single summary stat. For incremental
+/// scenarios this is a direct measure of how well incrementality is working.
+fn store_query_cache_hit_rate_into_stats(stats: &mut Stats, profile: &SelfProfile) {
+    // Queries that were never invoked contribute to neither total, so a run
+    // without any recorded query invocations simply omits the stat.
+    if profile.query_invocation_count > 0 {
+        stats.insert(
+            "query-cache-hit-rate".to_string(),
+            profile.query_cache_hits as f64 / profile.query_invocation_count as f64,
+        );
+    }
+}
+
+/// Sums the time rustc spent loading query results from the incremental cache
+/// and stores it as a top-level `incr-cache-load-time` stat, in seconds.
+///
+/// The stat is only recorded for incremental scenarios, since cache loading
+/// cannot occur in a non-incremental build. It is most meaningful for
+/// `IncrUnchanged`, where the near-no-op rebuild is dominated by cache
+/// deserialization.
+fn store_incr_cache_load_time_into_stats(
+    stats: &mut Stats,
+    profile: &SelfProfile,
+    scenario: Scenario,
+) {
+    if scenario.is_incr() && profile.incremental_load_time_ns > 0 {
+        stats.insert(
+            "incr-cache-load-time".to_string(),
+            profile.incremental_load_time_ns as f64 / 1e9,
+        );
     }
 }
 
@@ -486,20 +1250,66 @@ enum DeserializeStatError {
     XperfError(#[from] anyhow::Error),
     #[error("io error")]
     IOError(#[from] std::io::Error),
+    #[error("implausible value {} for statistic `{}`", .value, .stat)]
+    ImplausibleValue { stat: String, value: f64 },
+}
+
+/// Statistics that cannot plausibly be zero: every compilation executes
+/// instructions and takes time.
+const POSITIVE_STATS: &[&str] = &["instructions:u", "cycles:u", "cycles", "task-clock", "wall-time"];
+
+/// The default ceiling above which a measured value is considered implausible
+/// (e.g. due to counter wraparound or a kernel bug). This is several orders of
+/// magnitude above anything legitimately produced even by the largest
+/// benchmarks, to avoid false positives. It can be overridden through the
+/// `RUSTC_PERF_STAT_CEILING` environment variable.
+const STAT_VALUE_CEILING: f64 = 1e16;
+
+/// Plausibility check for a measured statistic, guarding against silently
+/// recording miscounted values that would pollute trend data. Implausible
+/// values are rejected like malformed output, which makes the caller retry
+/// the measurement.
+fn validate_stat_value(stat: &str, value: f64) -> Result<(), DeserializeStatError> {
+    let ceiling = env::var("RUSTC_PERF_STAT_CEILING")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(STAT_VALUE_CEILING);
+    let implausible = !value.is_finite()
+        || value < 0.0
+        || value > ceiling
+        || (value == 0.0 && POSITIVE_STATS.contains(&stat));
+    if implausible {
+        Err(DeserializeStatError::ImplausibleValue {
+            stat: stat.to_string(),
+            value,
+        })
+    } else {
+        Ok(())
+    }
 }
 
 enum SelfProfileFiles {
     Eight { file: PathBuf },
 }
 
-fn process_stat_output(
-    output: process::Output,
-) -> Result<(Stats, Option<SelfProfile>, Option<SelfProfileFiles>), DeserializeStatError> {
+/// The parsed output of one benchmarked invocation: the gathered statistics,
+/// the self-profile data (if enabled), and the `-Cmetadata` hash(es) of the
+/// final crate, if reported by `rustc-fake` (see `bencher::BenchProcessor` for
+/// how the latter is stored).
+type StatOutput = (
+    Stats,
+    Option<SelfProfile>,
+    Option<SelfProfileFiles>,
+    Option<String>,
+);
+
+fn process_stat_output(output: process::Output) -> Result<StatOutput, DeserializeStatError> {
     let stdout = String::from_utf8(output.stdout.clone()).expect("utf8 output");
     let mut stats = Stats::new();
 
     let mut self_profile_dir: Option<PathBuf> = None;
     let mut self_profile_crate: Option<String> = None;
+    let mut crate_metadata: Option<String> = None;
     for line in stdout.lines() {
         if let Some(stripped) = line.strip_prefix("!self-profile-dir:") {
             self_profile_dir = Some(PathBuf::from(stripped));
@@ -512,23 +1322,50 @@ fn process_stat_output(
         if let Some(counter_file) = line.strip_prefix("!counters-file:") {
             let counters = etw_parser::parse_etw_file(counter_file).unwrap();
 
-            stats.insert("cycles".into(), counters.total_cycles as f64);
+            stats.insert_integer("cycles".into(), counters.total_cycles);
+            stats.insert_integer("instructions:u".into(), counters.instructions_retired);
+            stats.insert("cpu-clock".into(), counters.cpu_clock);
+            continue;
+        }
+        if let Some(stripped) = line.strip_prefix("!crate-metadata:") {
+            crate_metadata = Some(String::from(stripped));
+            continue;
+        }
+        if let Some(stripped) = line.strip_prefix("!link-wall-time:") {
             stats.insert(
-                "instructions:u".into(),
-                counters.instructions_retired as f64,
+                "link-wall-time".into(),
+                stripped
+                    .parse()
+                    .map_err(|e| DeserializeStatError::ParseError(stripped.to_string(), e))?,
             );
-            stats.insert("cpu-clock".into(), counters.cpu_clock);
             continue;
         }
-        if let Some(stripped) = line.strip_prefix("!wall-time:") {
+        if let Some(stripped) = line.strip_prefix("!build-script-compile:") {
             stats.insert(
-                "wall-time".into(),
+                "build-script-compile".into(),
                 stripped
                     .parse()
                     .map_err(|e| DeserializeStatError::ParseError(stripped.to_string(), e))?,
             );
             continue;
         }
+        if let Some(stripped) = line.strip_prefix("!build-script-run:") {
+            stats.insert(
+                "build-script-run".into(),
+                stripped
+                    .parse()
+                    .map_err(|e| DeserializeStatError::ParseError(stripped.to_string(), e))?,
+            );
+            continue;
+        }
+        if let Some(stripped) = line.strip_prefix("!wall-time:") {
+            let value = stripped
+                .parse()
+                .map_err(|e| DeserializeStatError::ParseError(stripped.to_string(), e))?;
+            validate_stat_value("wall-time", value)?;
+            stats.insert("wall-time".into(), value);
+            continue;
+        }
 
         // The rest of the loop body handles processing output from the Linux `perf` tool
         // so on Windows, we just skip it and go to the next line.
@@ -557,17 +1394,54 @@ fn process_stat_output(
         if cnt == "<not supported>" || cnt == "<not counted>" || cnt.is_empty() {
             continue;
         }
+        // When more events are requested than the PMU has slots, the kernel
+        // multiplexes them and perf reports the percentage of time each event
+        // was actually counted. `perf stat` already extrapolates the printed
+        // count to the full runtime, so record it as-is instead of aborting
+        // the collection; the value is an estimate, so warn about it.
         if !pct.starts_with("100.") {
-            panic!(
-                "measurement of `{}` only active for {}% of the time",
-                name, pct
-            );
+            match pct.parse::<f64>() {
+                Ok(active) if active > 0.0 => {
+                    log::warn!(
+                        "measurement of `{name}` only active for {pct}% of the time \
+                         (perf event multiplexing); the value is an extrapolated estimate"
+                    );
+                }
+                _ => {
+                    log::warn!(
+                        "measurement of `{name}` reports invalid active percentage `{pct}`; \
+                         line skipped"
+                    );
+                    continue;
+                }
+            }
+        }
+        // Counters that perf reports as plain integers (instructions, cache
+        // misses, ...) take the exact integer path; fractional values like
+        // `task-clock` stay floats.
+        if let Ok(value) = cnt.parse::<u64>() {
+            validate_stat_value(name, value as f64)?;
+            stats.insert_integer(name.to_owned(), value);
+        } else {
+            let value: f64 = cnt
+                .parse()
+                .map_err(|e| DeserializeStatError::ParseError(cnt.to_string(), e))?;
+            validate_stat_value(name, value)?;
+            stats.insert(name.to_owned(), value);
+        }
+    }
+
+    // Per-pass timings from `-Ztime-passes` (see `RUSTC_PERF_TIME_PASSES`),
+    // which rustc prints to stderr.
+    if env::var_os("RUSTC_PERF_TIME_PASSES").is_some() {
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            if let Some((time, rss, pass)) = parse_time_passes_line(line) {
+                stats.insert(format!("pass:{}:time", pass), time);
+                if let Some(rss) = rss {
+                    stats.insert(format!("pass:{}:rss", pass), rss);
+                }
+            }
         }
-        stats.insert(
-            name.to_owned(),
-            cnt.parse()
-                .map_err(|e| DeserializeStatError::ParseError(cnt.to_string(), e))?,
-        );
     }
 
     if stats.is_empty() {
@@ -577,12 +1451,68 @@ fn process_stat_output(
         (Some(dir), Some(krate)) => parse_self_profile(dir, krate)?,
         _ => (None, None),
     };
-    Ok((stats, profile, files))
+    Ok((stats, profile, files, crate_metadata))
+}
+
+/// Parses one `-Ztime-passes` line, e.g.
+/// `time:   0.026; rss:   67MB ->  107MB (  +40MB)\tparse_crate`, returning
+/// the wall time of the pass in seconds, the resident set size after the
+/// pass in bytes (when reported) and the pass name.
+fn parse_time_passes_line(line: &str) -> Option<(f64, Option<f64>, &str)> {
+    let rest = line.trim_start().strip_prefix("time:")?;
+    let (metrics, pass) = rest.rsplit_once('\t')?;
+    let pass = pass.trim();
+    if pass.is_empty() {
+        return None;
+    }
+    let mut parts = metrics.split(';');
+    let time: f64 = parts.next()?.trim().parse().ok()?;
+    let rss = parts.next().and_then(|rss| {
+        let after = rss.trim().strip_prefix("rss:")?.split("->").nth(1)?;
+        let after = after.split_whitespace().next()?;
+        let megabytes: f64 = after.strip_suffix("MB")?.parse().ok()?;
+        Some(megabytes * 1_000_000.0)
+    });
+    Some((time, rss, pass))
+}
+
+/// A single measured statistic value.
+///
+/// Counters that are conceptually integers (instruction counts, artifact
+/// sizes, event counts) are kept as `u64`, so they round-trip exactly even
+/// above 2^53, where `f64` can no longer represent every integer. Genuinely
+/// real-valued metrics (wall-time, rates) stay `f64`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatValue {
+    Integer(u64),
+    Float(f64),
+}
+
+impl StatValue {
+    /// The value as a float, for consumers that do not need exactness.
+    /// Integers above 2^53 lose precision in the conversion.
+    pub fn as_f64(self) -> f64 {
+        match self {
+            StatValue::Integer(value) => value as f64,
+            StatValue::Float(value) => value,
+        }
+    }
+}
+
+/// Serializes as a plain JSON number: integer counters as integers (so they
+/// round-trip exactly), real-valued metrics as floats.
+impl serde::Serialize for StatValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            StatValue::Integer(value) => serializer.serialize_u64(*value),
+            StatValue::Float(value) => serializer.serialize_f64(*value),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Stats {
-    stats: HashMap<String, f64>,
+    stats: HashMap<String, StatValue>,
 }
 
 impl Default for Stats {
@@ -591,6 +1521,15 @@ impl Default for Stats {
     }
 }
 
+/// Serializes as a map of statistic name to value, for consumers that want
+/// the raw numbers (e.g. the `--json-stdout` output) without the database
+/// layer.
+impl serde::Serialize for Stats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.stats.iter())
+    }
+}
+
 impl Stats {
     pub fn new() -> Stats {
         Stats {
@@ -599,6 +1538,10 @@ impl Stats {
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> + '_ {
+        self.stats.iter().map(|(k, v)| (k.as_str(), v.as_f64()))
+    }
+
+    pub fn iter_values(&self) -> impl Iterator<Item = (&str, StatValue)> + '_ {
         self.stats.iter().map(|(k, v)| (k.as_str(), *v))
     }
 
@@ -607,13 +1550,39 @@ impl Stats {
     }
 
     pub fn insert(&mut self, stat: String, value: f64) {
-        self.stats.insert(stat, value);
+        self.stats.insert(stat, StatValue::Float(value));
+    }
+
+    pub fn insert_integer(&mut self, stat: String, value: u64) {
+        self.stats.insert(stat, StatValue::Integer(value));
+    }
+
+    pub fn get(&self, stat: &str) -> Option<f64> {
+        self.stats.get(stat).map(|value| value.as_f64())
+    }
+
+    pub fn get_value(&self, stat: &str) -> Option<StatValue> {
+        self.stats.get(stat).copied()
+    }
+
+    pub fn remove(&mut self, stat: &str) -> Option<f64> {
+        self.stats.remove(stat).map(|value| value.as_f64())
     }
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct SelfProfile {
     pub artifact_sizes: Vec<ArtifactSize>,
+    /// Total number of query invocations, across all queries.
+    #[serde(default)]
+    pub query_invocation_count: u64,
+    /// Total number of query cache hits, across all queries.
+    #[serde(default)]
+    pub query_cache_hits: u64,
+    /// Total time spent loading query results from the incremental cache,
+    /// across all queries, in nanoseconds.
+    #[serde(default)]
+    pub incremental_load_time_ns: u64,
 }
 
 fn parse_self_profile(
@@ -642,8 +1611,19 @@ fn parse_self_profile(
                 std::io::Error::new(ErrorKind::InvalidData, error)
             })?
             .perform_analysis();
+        let mut query_invocation_count = 0u64;
+        let mut query_cache_hits = 0u64;
+        let mut incremental_load_time_ns = 0u64;
+        for query in &results.query_data {
+            query_invocation_count += query.invocation_count as u64;
+            query_cache_hits += query.number_of_cache_hits as u64;
+            incremental_load_time_ns += query.incremental_load_time.as_nanos() as u64;
+        }
         let profile = SelfProfile {
             artifact_sizes: results.artifact_sizes,
+            query_invocation_count,
+            query_cache_hits,
+            incremental_load_time_ns,
         };
         let files = SelfProfileFiles::Eight { file: profile_path };
         (Some(profile), Some(files))
@@ -654,3 +1634,209 @@ fn parse_self_profile(
     };
     Ok((profile, files))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        process_stat_output, validate_stat_value, DeserializeStatError, StatOutput, StatValue,
+        Stats,
+    };
+    use std::process;
+
+    /// Builds a `process::Output` around recorded tool output so that
+    /// `process_stat_output` can be exercised against real captured fixtures
+    /// (see the `fixtures` directory) without running any tool.
+    fn replay(stdout: &str) -> Result<StatOutput, DeserializeStatError> {
+        #[cfg(unix)]
+        use std::os::unix::process::ExitStatusExt;
+        #[cfg(windows)]
+        use std::os::windows::process::ExitStatusExt;
+
+        process_stat_output(process::Output {
+            status: process::ExitStatus::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn parses_time_passes_line() {
+        let (time, rss, pass) = super::parse_time_passes_line(
+            "time:   0.026; rss:   67MB ->  107MB (  +40MB)\tparse_crate",
+        )
+        .unwrap();
+        assert_eq!(pass, "parse_crate");
+        assert!((time - 0.026).abs() < 1e-9);
+        assert_eq!(rss, Some(107_000_000.0));
+
+        // Lines without an RSS report still yield the time.
+        let (time, rss, pass) = super::parse_time_passes_line("time:   1.5\ttotal").unwrap();
+        assert_eq!(pass, "total");
+        assert!((time - 1.5).abs() < 1e-9);
+        assert_eq!(rss, None);
+
+        assert!(super::parse_time_passes_line("warning: unused variable").is_none());
+    }
+
+    #[test]
+    fn translates_touch_file_globs() {
+        let regex = super::glob_to_regex("src/**/*.rs").unwrap();
+        assert!(regex.is_match("src/lib.rs"));
+        assert!(regex.is_match("src/a/b/mod.rs"));
+        assert!(!regex.is_match("src/lib.rs.orig"));
+        assert!(!regex.is_match("other/lib.rs"));
+
+        let regex = super::glob_to_regex("src/l?b.rs").unwrap();
+        assert!(regex.is_match("src/lib.rs"));
+        assert!(!regex.is_match("src/l/b.rs"));
+    }
+
+    // The perf stat fixtures rely on the `perf` line format, which is only
+    // parsed on non-Windows platforms.
+    #[cfg(not(windows))]
+    #[test]
+    fn replay_perf_stat() {
+        let (stats, profile, files, crate_metadata) =
+            replay(include_str!("fixtures/perf_stat.txt")).unwrap();
+        assert_eq!(stats.get("instructions:u"), Some(10837678456.0));
+        assert_eq!(stats.get("cycles:u"), Some(4745269934.0));
+        assert_eq!(stats.get("task-clock"), Some(1330.385480));
+        assert_eq!(stats.get("context-switches"), Some(142.0));
+        assert_eq!(stats.get("wall-time"), Some(1.3340759));
+        // `<not supported>` and `<not counted>` counters must be skipped.
+        assert_eq!(stats.get("branch-misses"), None);
+        assert_eq!(stats.get("cache-misses"), None);
+        assert!(profile.is_none());
+        assert!(files.is_none());
+        assert!(crate_metadata.is_none());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn replay_perf_stat_with_self_profile_markers() {
+        // Point the self-profile marker at an empty directory: the markers are
+        // parsed, but no `.mm_profdata` file is found, so no profile is
+        // returned.
+        let dir = tempfile::tempdir().unwrap();
+        let stdout = include_str!("fixtures/perf_stat_self_profile.txt")
+            .replace("$SELF_PROFILE_DIR", dir.path().to_str().unwrap());
+        let (stats, profile, files, crate_metadata) = replay(&stdout).unwrap();
+        assert_eq!(stats.get("instructions:u"), Some(10837678456.0));
+        assert!(profile.is_none());
+        assert!(files.is_none());
+        assert_eq!(crate_metadata.as_deref(), Some("3e524b9e4d4e3569"));
+    }
+
+    #[test]
+    fn replay_etw_counters_file() {
+        let counters_file = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/compile/execute/fixtures/pmc_counters.txt"
+        );
+        let (stats, ..) = replay(&format!("!counters-file:{counters_file}\n")).unwrap();
+        // The deltas of the Pmc events while rustc was scheduled on the CPU.
+        assert_eq!(stats.get("instructions:u"), Some(1021453.0));
+        assert_eq!(stats.get("cycles"), Some(362837.0));
+    }
+
+    #[test]
+    fn replay_malformed_output() {
+        match replay(include_str!("fixtures/malformed.txt")) {
+            Err(DeserializeStatError::NoOutput(_)) => {}
+            other => panic!("expected NoOutput error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn replay_empty_output() {
+        match replay("") {
+            Err(DeserializeStatError::NoOutput(_)) => {}
+            other => panic!("expected NoOutput error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    // A counter above 2^53 must round-trip exactly through the integer path;
+    // the float view of the same value demonstrably cannot represent it.
+    #[cfg(not(windows))]
+    #[test]
+    fn replay_integer_counter_above_2_pow_53() {
+        let big = (1u64 << 53) + 1;
+        let stdout = format!("{big};;instructions:u;1297037;100.00\n!wall-time:1.0\n");
+        let (stats, ..) = replay(&stdout).unwrap();
+        assert_eq!(
+            stats.get_value("instructions:u"),
+            Some(StatValue::Integer(big))
+        );
+        assert_ne!(stats.get("instructions:u").unwrap() as u64, big);
+    }
+
+    // A multiplexed counter (active for less than 100% of the time) is
+    // recorded as printed instead of aborting the collection; `perf stat`
+    // already extrapolates the count to the full runtime.
+    #[cfg(not(windows))]
+    #[test]
+    fn replay_records_multiplexed_counter_as_printed() {
+        let stdout = "500;;cache-misses;1297037;50.00\n!wall-time:1.0\n";
+        let (stats, ..) = replay(stdout).unwrap();
+        assert_eq!(
+            stats.get_value("cache-misses"),
+            Some(StatValue::Integer(500))
+        );
+    }
+
+    #[test]
+    fn integer_stats_round_trip_exactly() {
+        let big = (1u64 << 53) + 1;
+        let mut stats = Stats::new();
+        stats.insert_integer("instructions:u".to_string(), big);
+        assert_eq!(
+            stats.get_value("instructions:u"),
+            Some(StatValue::Integer(big))
+        );
+        // Fractional values keep the float representation.
+        stats.insert("task-clock".to_string(), 1330.385480);
+        assert_eq!(
+            stats.get_value("task-clock"),
+            Some(StatValue::Float(1330.385480))
+        );
+    }
+
+    #[track_caller]
+    fn assert_rejected(stat: &str, value: f64) {
+        match validate_stat_value(stat, value) {
+            Err(DeserializeStatError::ImplausibleValue { .. }) => {}
+            other => panic!("expected `{stat}` = {value} to be rejected, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn accept_plausible_values() {
+        assert!(validate_stat_value("instructions:u", 1e9).is_ok());
+        assert!(validate_stat_value("wall-time", 0.02).is_ok());
+        // Zero is fine for statistics that can legitimately be zero.
+        assert!(validate_stat_value("context-switches", 0.0).is_ok());
+    }
+
+    #[test]
+    fn reject_negative_values() {
+        assert_rejected("instructions:u", -1.0);
+        assert_rejected("context-switches", -5.0);
+    }
+
+    #[test]
+    fn reject_zero_for_positive_stats() {
+        assert_rejected("instructions:u", 0.0);
+        assert_rejected("wall-time", 0.0);
+    }
+
+    #[test]
+    fn reject_absurdly_large_values() {
+        assert_rejected("instructions:u", 1e17);
+    }
+
+    #[test]
+    fn reject_non_finite_values() {
+        assert_rejected("cycles:u", f64::NAN);
+        assert_rejected("cycles:u", f64::INFINITY);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::compile::execute::{PerfTool, ProcessOutputData, Processor, Retry};
+use crate::compile::execute::{PerfTool, ProcessOutputData, Processor, ProcessorArtifact, Retry};
 use crate::utils;
 use crate::utils::cachegrind::cachegrind_annotate;
 use anyhow::Context;
@@ -24,6 +24,7 @@ pub enum Profiler {
     DhatCopy,
     Massif,
     Bytehound,
+    Heaptrack,
     Eprintln,
     LlvmLines,
     MonoItems,
@@ -43,6 +44,7 @@ impl Profiler {
                 | Profiler::DhatCopy
                 | Profiler::Massif
                 | Profiler::Bytehound
+                | Profiler::Heaptrack
                 | Profiler::Eprintln
                 | Profiler::LlvmLines
                 | Profiler::LlvmIr
@@ -50,27 +52,121 @@ impl Profiler {
                 | Profiler::DepGraph
         )
     }
+
+    /// Checks that the external tool this profiler wraps the measured rustc
+    /// in is actually installed, so that a missing binary is reported up
+    /// front instead of after minutes of benchmark preparation. Tools that
+    /// are only needed for postprocessing (e.g. `opannotate`,
+    /// `callgrind_annotate`) are not probed here; their absence only loses
+    /// the derived output, not the profile itself.
+    pub fn check_available(&self) -> anyhow::Result<()> {
+        let binary = match self {
+            // Implemented by rustc itself (`-Zself-profile`, `-Ztime-passes`,
+            // `-Zdump-mono-stats`, `-Zdump-dep-graph`, `--emit=llvm-ir`);
+            // the measureme postprocessing tools are checked separately.
+            Profiler::SelfProfile
+            | Profiler::Eprintln
+            | Profiler::MonoItems
+            | Profiler::DepGraph
+            | Profiler::LlvmIr => return Ok(()),
+            Profiler::PerfRecord => "perf",
+            Profiler::Oprofile => "operf",
+            Profiler::Samply => "samply",
+            Profiler::Cachegrind
+            | Profiler::Callgrind
+            | Profiler::Dhat
+            | Profiler::DhatCopy
+            | Profiler::Massif => "valgrind",
+            Profiler::Heaptrack => "heaptrack",
+            Profiler::LlvmLines => "cargo-llvm-lines",
+            Profiler::Bytehound => {
+                // Bytehound is injected via `LD_PRELOAD=libbytehound.so`
+                // rather than a wrapper binary. The dynamic loader only warns
+                // on stderr when a preload object cannot be found, so probe a
+                // trivial command and look for that warning.
+                let output = Command::new("true")
+                    .env("LD_PRELOAD", "libbytehound.so")
+                    .output();
+                if let Ok(output) = output {
+                    if String::from_utf8_lossy(&output.stderr).contains("cannot be preloaded") {
+                        anyhow::bail!(
+                            "the Bytehound profiler requires `libbytehound.so`, \
+                             which cannot be preloaded; make sure it is in the \
+                             dynamic loader's search path"
+                        );
+                    }
+                }
+                return Ok(());
+            }
+        };
+        if utils::is_installed(binary) {
+            Ok(())
+        } else {
+            anyhow::bail!(
+                "the {:?} profiler requires `{}`, which does not appear to be installed",
+                self,
+                binary
+            )
+        }
+    }
 }
 
 pub struct ProfileProcessor<'a> {
     profiler: Profiler,
     output_dir: &'a Path,
     id: &'a str,
+    /// Only profile the first iteration; later iterations are plain builds.
+    /// Useful for expensive profilers (e.g. Callgrind, DHAT) whose output is
+    /// essentially deterministic, so profiling every iteration is wasted work.
+    profile_only_first_iteration: bool,
+    is_first_collection: bool,
+    /// The artifacts written to `output_dir` so far, tracked so that callers
+    /// can locate them through `Processor::output_artifacts` without
+    /// re-deriving the file naming scheme.
+    artifacts: Vec<ProcessorArtifact>,
 }
 
 impl<'a> ProfileProcessor<'a> {
-    pub fn new(profiler: Profiler, output_dir: &'a Path, id: &'a str) -> Self {
+    pub fn new(
+        profiler: Profiler,
+        output_dir: &'a Path,
+        id: &'a str,
+        profile_only_first_iteration: bool,
+    ) -> Self {
         ProfileProcessor {
             profiler,
             output_dir,
             id,
+            profile_only_first_iteration,
+            is_first_collection: true,
+            artifacts: Vec::new(),
         }
     }
+
+    fn is_profiled_iteration(&self) -> bool {
+        self.is_first_collection || !self.profile_only_first_iteration
+    }
 }
 
 impl<'a> Processor for ProfileProcessor<'a> {
     fn perf_tool(&self) -> PerfTool {
-        PerfTool::ProfileTool(self.profiler)
+        if self.is_profiled_iteration() {
+            PerfTool::ProfileTool(self.profiler)
+        } else {
+            // A plain build without the profiler attached. `Eprintln` just
+            // runs rustc while capturing its stderr, which we then ignore in
+            // `process_output` below.
+            PerfTool::ProfileTool(Profiler::Eprintln)
+        }
+    }
+
+    fn start_first_collection(&mut self) {
+        self.is_first_collection = true;
+    }
+
+    fn finished_first_collection(&mut self) -> bool {
+        self.is_first_collection = false;
+        false
     }
 
     fn process_output<'b>(
@@ -79,6 +175,12 @@ impl<'a> Processor for ProfileProcessor<'a> {
         output: process::Output,
     ) -> Pin<Box<dyn Future<Output = anyhow::Result<Retry>> + 'b>> {
         Box::pin(async move {
+            if !self.is_profiled_iteration() {
+                // A plain re-build on a later iteration: there is nothing to
+                // post-process, and we must not overwrite the artifacts saved
+                // from the profiled first iteration.
+                return Ok(Retry::No);
+            }
             fs::create_dir_all(self.output_dir)?;
 
             // Produce a name of the form $PREFIX-$ID-$BENCHMARK-$PROFILE-$SCENARIO.
@@ -291,6 +393,42 @@ impl<'a> Processor for ProfileProcessor<'a> {
                     fs::copy(tmp_bytehound_file, target_file)?;
                 }
 
+                // Heaptrack produces (via rustc-fake) a data file called
+                // `heaptrack.<binary>.<pid>.gz` in the build directory. We
+                // move it to the output dir, giving it a new name in the
+                // process, and post-process it with `heaptrack --analyze` to
+                // produce a human-readable analysis in the output dir. The
+                // headline peak heap consumption is additionally reported as
+                // a `size:heaptrack_peak_bytes` stat.
+                Profiler::Heaptrack => {
+                    let tmp_heaptrack_file = fs::read_dir(data.cwd)?
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                        .find(|path| {
+                            path.file_name()
+                                .and_then(|name| name.to_str())
+                                .is_some_and(|name| name.starts_with("heaptrack."))
+                        })
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("no heaptrack output found in {:?}", data.cwd)
+                        })?;
+                    let htout_file = filepath(self.output_dir, &out_file("htout"));
+                    let htann_file = filepath(self.output_dir, &out_file("htann"));
+
+                    utils::fs::rename(&tmp_heaptrack_file, &htout_file)?;
+
+                    let mut ht_analyze_cmd = Command::new("heaptrack");
+                    ht_analyze_cmd.arg("--analyze").arg(&htout_file);
+                    let analysis = ht_analyze_cmd
+                        .output()
+                        .context("heaptrack --analyze")?
+                        .stdout;
+                    if let Some(peak) = parse_heaptrack_peak(&String::from_utf8_lossy(&analysis)) {
+                        eprintln!("size:heaptrack_peak_bytes: {peak}");
+                    }
+                    fs::write(htann_file, analysis)?;
+                }
+
                 // `eprintln!` statements are redirected (via rustc-fake) to a file
                 // called `eprintln`. We copy it from the temp dir to the output
                 // dir, giving it a new name in the process.
@@ -397,7 +535,75 @@ impl<'a> Processor for ProfileProcessor<'a> {
                     fs::write(ll_file, output.stdout)?;
                 }
             }
+
+            // Remember where the artifacts of this invocation went.
+            let suffix = format!(
+                "-{}-{}-{:?}-{}",
+                self.id, data.name, data.profile, data.scenario_str
+            );
+            for entry in fs::read_dir(self.output_dir)? {
+                let entry = entry?;
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                if file_name.contains(&suffix)
+                    && !self.artifacts.iter().any(|a| a.path == entry.path())
+                {
+                    self.artifacts.push(ProcessorArtifact {
+                        profile: data.profile,
+                        scenario: data.scenario_str.to_string(),
+                        path: entry.path(),
+                    });
+                }
+            }
+
             Ok(Retry::No)
         })
     }
+
+    fn output_artifacts(&self) -> Vec<ProcessorArtifact> {
+        self.artifacts.clone()
+    }
+}
+
+/// Extracts the peak heap consumption, in bytes, from the output of
+/// `heaptrack --analyze`, which reports it in a humanized form (e.g.
+/// `peak heap memory consumption: 104.86M`).
+fn parse_heaptrack_peak(analysis: &str) -> Option<u64> {
+    let line = analysis
+        .lines()
+        .find(|line| line.contains("peak heap memory consumption:"))?;
+    let value = line.rsplit(':').next()?.trim();
+    // Depending on the heaptrack version the unit is printed either as a
+    // single letter (`104.86M`) or with a trailing `B` (`104.86MB`), so strip
+    // the `B` before looking at the multiplier.
+    let value = value.strip_suffix('B').unwrap_or(value);
+    let (number, factor) = match value.as_bytes().last()? {
+        b'K' => (&value[..value.len() - 1], 1u64 << 10),
+        b'M' => (&value[..value.len() - 1], 1 << 20),
+        b'G' => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    Some((number.trim().parse::<f64>().ok()? * factor as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_heaptrack_peak;
+
+    #[test]
+    fn parses_heaptrack_peak_suffixes() {
+        let analysis = |value: &str| format!("total runtime: 1.23s\npeak heap memory consumption: {value}\ntotal memory leaked: 0B\n");
+        assert_eq!(parse_heaptrack_peak(&analysis("1024")), Some(1024));
+        assert_eq!(parse_heaptrack_peak(&analysis("1024B")), Some(1024));
+        assert_eq!(
+            parse_heaptrack_peak(&analysis("104.86M")),
+            Some((104.86f64 * (1u64 << 20) as f64) as u64)
+        );
+        assert_eq!(
+            parse_heaptrack_peak(&analysis("104.86MB")),
+            parse_heaptrack_peak(&analysis("104.86M"))
+        );
+        assert_eq!(parse_heaptrack_peak(&analysis("2K")), Some(2048));
+        assert_eq!(parse_heaptrack_peak(&analysis("1.5GB")), Some(3 * (1 << 29)));
+        assert_eq!(parse_heaptrack_peak("no peak line here"), None);
+    }
 }
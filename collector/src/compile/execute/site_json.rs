@@ -0,0 +1,186 @@
+//! Output of collected statistics in the JSON shape used by the site's
+//! comparison endpoint.
+//!
+//! The field names and value formats deliberately mirror the `comparison`
+//! module of `site/src/api.rs`: test cases are identified by `benchmark`,
+//! `profile`, `scenario` and `backend` with the same string representations
+//! the database uses (`check`, `incr-patched-println`, `llvm`, ...), and
+//! statistics keep the raw stat names the frontend already knows
+//! (`instructions:u`, `wall-time`, `size:*`). A fork of the site can
+//! therefore build a comparison between two such files without going through
+//! the database at all.
+
+use crate::compile::benchmark::codegen_backend::CodegenBackend;
+use crate::compile::benchmark::profile::Profile;
+use crate::compile::benchmark::scenario::Scenario;
+use crate::compile::execute;
+use crate::compile::execute::bencher::Bencher;
+use crate::compile::execute::{
+    DeserializeStatError, PerfTool, ProcessOutputData, Processor, Retry,
+};
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process;
+
+/// Everything collected for a single artifact.
+#[derive(Debug, serde::Serialize)]
+pub struct Report {
+    /// Name of the benchmarked artifact, in the same format as
+    /// `ArtifactDescription::commit` (a commit sha or a tag like `1.70.0`).
+    pub commit: String,
+    /// One entry per executed iteration of each test case.
+    pub compile_stats: Vec<TestResult>,
+}
+
+/// The statistics gathered by one iteration of a single test case.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TestResult {
+    pub benchmark: String,
+    pub profile: String,
+    pub scenario: String,
+    pub backend: String,
+    /// Stat name to value, e.g. `"instructions:u": 250916.0`. A `BTreeMap`
+    /// so that repeated runs produce byte-identical, diffable files.
+    pub statistics: BTreeMap<String, f64>,
+}
+
+/// Processor that gathers the statistics of every test case into a [`Report`]
+/// and writes it to a JSON file instead of storing anything into a database.
+/// The file is rewritten after each benchmark, so it is complete up to the
+/// last finished benchmark even if the collection is interrupted.
+pub struct SiteJsonProcessor {
+    path: PathBuf,
+    report: Report,
+    tries: u8,
+    discard_results: bool,
+}
+
+impl SiteJsonProcessor {
+    pub fn new(commit: String, path: PathBuf) -> Self {
+        SiteJsonProcessor {
+            path,
+            report: Report {
+                commit,
+                compile_stats: Vec::new(),
+            },
+            tries: 0,
+            discard_results: false,
+        }
+    }
+
+    /// Returns the report accumulated so far.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Serializes the accumulated report to the configured path.
+    pub fn write(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self.report)?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("cannot write site JSON report to {:?}", self.path))
+    }
+}
+
+impl Processor for SiteJsonProcessor {
+    fn perf_tool(&self) -> PerfTool {
+        if cfg!(target_os = "macos") {
+            PerfTool::BenchTool(Bencher::TimeStat)
+        } else if cfg!(unix) {
+            PerfTool::BenchTool(Bencher::PerfStat)
+        } else {
+            PerfTool::BenchTool(Bencher::XperfStat)
+        }
+    }
+
+    fn set_discard_results(&mut self, discard: bool) {
+        self.discard_results = discard;
+    }
+
+    fn process_output<'b>(
+        &'b mut self,
+        data: &'b ProcessOutputData<'_>,
+        output: process::Output,
+    ) -> Pin<Box<dyn Future<Output = anyhow::Result<Retry>> + 'b>> {
+        Box::pin(async move {
+            match execute::process_stat_output(output) {
+                Ok((stats, ..)) => {
+                    if self.discard_results {
+                        return Ok(Retry::No);
+                    }
+                    // The same database string representations the site's
+                    // frontend uses to identify a test case.
+                    let scenario = match data.scenario {
+                        Scenario::Full => database::Scenario::Empty,
+                        Scenario::IncrFull => database::Scenario::IncrementalEmpty,
+                        Scenario::IncrUnchanged => database::Scenario::IncrementalFresh,
+                        Scenario::IncrPatched => {
+                            database::Scenario::IncrementalPatch(data.patch.unwrap().name)
+                        }
+                        Scenario::IncrReverted => database::Scenario::IncrementalPatch(
+                            format!("{} (reverted)", data.patch.unwrap().name)
+                                .as_str()
+                                .into(),
+                        ),
+                    };
+                    let profile = match data.profile {
+                        Profile::Check => database::Profile::Check,
+                        Profile::Debug => database::Profile::Debug,
+                        Profile::Doc => database::Profile::Doc,
+                        Profile::Opt => database::Profile::Opt,
+                        Profile::OptDebuginfo => database::Profile::OptDebuginfo,
+                        Profile::Clippy => database::Profile::Clippy,
+                    };
+                    let backend = match data.backend {
+                        CodegenBackend::Llvm => database::CodegenBackend::Llvm,
+                        CodegenBackend::Cranelift => database::CodegenBackend::Cranelift,
+                    };
+                    self.report.compile_stats.push(TestResult {
+                        benchmark: data.name.0.clone(),
+                        profile: profile.to_string(),
+                        scenario: scenario.to_id(),
+                        backend: backend.as_str().to_string(),
+                        statistics: stats
+                            .iter()
+                            .map(|(stat, value)| (stat.to_string(), value))
+                            .collect(),
+                    });
+                    Ok(Retry::No)
+                }
+                Err(
+                    error @ (DeserializeStatError::NoOutput(_)
+                    | DeserializeStatError::ImplausibleValue { .. }),
+                ) => {
+                    if self.tries < 5 {
+                        log::warn!(
+                            "failed to gather stats, retrying (try {}): {}",
+                            self.tries,
+                            error
+                        );
+                        self.tries += 1;
+                        Ok(Retry::Yes)
+                    } else {
+                        Err(anyhow::anyhow!(
+                            "failed to collect statistics after 5 tries: {}",
+                            error
+                        ))
+                    }
+                }
+                Err(error) => Err(error.into()),
+            }
+        })
+    }
+
+    fn postprocess_results<'b>(&'b mut self) -> Pin<Box<dyn Future<Output = ()> + 'b>> {
+        Box::pin(async move {
+            if let Err(error) = self.write() {
+                eprintln!(
+                    "collector error: failed to write site JSON report: {:#}",
+                    error
+                );
+            }
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Declarative post-processing of benchmark statistics.
+//!
+//! Different analysis backends expect different stat key conventions and
+//! units. Instead of hardcoding conversions in the collector, a transform is
+//! a list of rules loaded from a JSON file and applied to every measured
+//! [`Stats`] before it is recorded. The default transform is empty, i.e. the
+//! recorded statistics are unchanged.
+//!
+//! Example transform file:
+//!
+//! ```json
+//! [
+//!     { "rule": "rename", "from": "task-clock", "to": "task-clock-msec" },
+//!     { "rule": "scale", "stat": "max-rss", "factor": 1024.0 },
+//!     { "rule": "derive", "name": "ipc",
+//!       "numerator": "instructions:u", "denominator": "cycles:u" }
+//! ]
+//! ```
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use super::Stats;
+
+/// A single transformation applied to a set of statistics.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(tag = "rule", rename_all = "kebab-case")]
+pub enum StatTransformRule {
+    /// Renames the statistic `from` to `to`. Does nothing when `from` was not
+    /// measured.
+    Rename { from: String, to: String },
+    /// Multiplies the value of `stat` by `factor`, e.g. to convert units.
+    Scale { stat: String, factor: f64 },
+    /// Adds a new statistic `name` computed as `numerator / denominator` of
+    /// two measured statistics. Skipped when either input is missing or the
+    /// denominator is zero.
+    Derive {
+        name: String,
+        numerator: String,
+        denominator: String,
+    },
+}
+
+/// An ordered list of [`StatTransformRule`]s. Rules are applied in order, so
+/// later rules observe the effects of earlier ones.
+#[derive(Clone, Debug, Default)]
+pub struct StatTransform {
+    rules: Vec<StatTransformRule>,
+}
+
+impl StatTransform {
+    pub fn from_file(path: &Path) -> anyhow::Result<StatTransform> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read stat transform file `{}`", path.display()))?;
+        let rules = serde_json::from_str(&text)
+            .with_context(|| format!("cannot parse stat transform file `{}`", path.display()))?;
+        Ok(StatTransform { rules })
+    }
+
+    pub fn apply(&self, stats: &mut Stats) {
+        for rule in &self.rules {
+            match rule {
+                StatTransformRule::Rename { from, to } => {
+                    if let Some(value) = stats.remove(from) {
+                        stats.insert(to.clone(), value);
+                    }
+                }
+                StatTransformRule::Scale { stat, factor } => {
+                    if let Some(value) = stats.get(stat) {
+                        stats.insert(stat.clone(), value * factor);
+                    }
+                }
+                StatTransformRule::Derive {
+                    name,
+                    numerator,
+                    denominator,
+                } => {
+                    if let (Some(numerator), Some(denominator)) =
+                        (stats.get(numerator), stats.get(denominator))
+                    {
+                        if denominator != 0.0 {
+                            stats.insert(name.clone(), numerator / denominator);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StatTransform, StatTransformRule};
+    use crate::compile::execute::Stats;
+
+    fn stats(entries: &[(&str, f64)]) -> Stats {
+        let mut stats = Stats::new();
+        for (name, value) in entries {
+            stats.insert(name.to_string(), *value);
+        }
+        stats
+    }
+
+    #[test]
+    fn rename() {
+        let transform = StatTransform {
+            rules: vec![StatTransformRule::Rename {
+                from: "task-clock".to_string(),
+                to: "task-clock-msec".to_string(),
+            }],
+        };
+        let mut stats = stats(&[("task-clock", 42.0)]);
+        transform.apply(&mut stats);
+        assert_eq!(stats.get("task-clock"), None);
+        assert_eq!(stats.get("task-clock-msec"), Some(42.0));
+    }
+
+    #[test]
+    fn scale() {
+        let transform = StatTransform {
+            rules: vec![StatTransformRule::Scale {
+                stat: "max-rss".to_string(),
+                factor: 1024.0,
+            }],
+        };
+        let mut stats = stats(&[("max-rss", 2.0)]);
+        transform.apply(&mut stats);
+        assert_eq!(stats.get("max-rss"), Some(2048.0));
+    }
+
+    #[test]
+    fn derive() {
+        let transform = StatTransform {
+            rules: vec![StatTransformRule::Derive {
+                name: "ipc".to_string(),
+                numerator: "instructions:u".to_string(),
+                denominator: "cycles:u".to_string(),
+            }],
+        };
+        let mut stats = stats(&[("instructions:u", 1000.0), ("cycles:u", 500.0)]);
+        transform.apply(&mut stats);
+        assert_eq!(stats.get("ipc"), Some(2.0));
+
+        // A zero denominator does not produce a derived stat.
+        let mut stats = Stats::new();
+        stats.insert("instructions:u".to_string(), 1000.0);
+        stats.insert("cycles:u".to_string(), 0.0);
+        transform.apply(&mut stats);
+        assert_eq!(stats.get("ipc"), None);
+    }
+}
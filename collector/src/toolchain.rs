@@ -3,8 +3,8 @@ use crate::compile::benchmark::profile::Profile;
 use anyhow::{anyhow, Context};
 use log::debug;
 use std::ffi::OsStr;
-use std::fs::{self, File};
-use std::io::{BufReader, Read};
+use std::fs;
+use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{fmt, str};
@@ -146,14 +146,15 @@ impl SysrootDownload {
     }
 
     fn get_and_extract(&self, component: Component) -> anyhow::Result<()> {
-        let archive_path = self.directory.join(format!(
-            "{}-{}-{}.tar.xz",
-            self.rust_sha, self.triple, component,
-        ));
+        let archive_name = format!("{}-{}-{}.tar.xz", self.rust_sha, self.triple, component);
+        let archive_path = self.directory.join(&archive_name);
         if archive_path.exists() {
-            let reader = BufReader::new(File::open(&archive_path)?);
-            let decompress = XzDecoder::new(reader);
-            let extract = self.extract(component, decompress);
+            // A stale cached archive that no longer matches its configured
+            // checksum is treated like a failed extraction: it is removed and
+            // fetched again (the fresh download is then verified strictly).
+            let data = fs::read(&archive_path)?;
+            let extract = crate::utils::checksum::verify(&archive_name, &data)
+                .and_then(|()| self.extract(component, XzDecoder::new(Cursor::new(data))));
             match extract {
                 Ok(()) => return Ok(()),
                 Err(err) => {
@@ -175,7 +176,13 @@ impl SysrootDownload {
             let resp = reqwest::blocking::get(url)?;
             log::debug!("{}", resp.status());
             if resp.status().is_success() {
-                let reader = XzDecoder::new(BufReader::new(resp));
+                let data = resp.bytes()?;
+                // A download that does not match its configured checksum must
+                // not be used: corrupted or tampered sources would silently
+                // produce untrustworthy measurements.
+                crate::utils::checksum::verify(&archive_name, &data)
+                    .with_context(|| format!("verifying download of {url}"))?;
+                let reader = XzDecoder::new(Cursor::new(data));
                 match self.extract(component, reader) {
                     Ok(()) => return Ok(()),
                     Err(err) => {
@@ -606,6 +613,7 @@ fn get_lib_dir_from_rustc(rustc: &Path) -> anyhow::Result<PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
 
     #[test]
     fn fill_libraries() {
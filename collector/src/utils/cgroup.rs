@@ -0,0 +1,69 @@
+//! Helpers for running measured compilations inside a pre-configured
+//! cgroup v2.
+//!
+//! When the `RUSTC_PERF_CGROUP` environment variable points to a cgroup v2
+//! directory (e.g. `/sys/fs/cgroup/rustc-perf`), `rustc-fake` moves itself
+//! (and therefore the measured rustc) into that cgroup before compiling, so
+//! that the compilation runs under whatever memory/CPU limits are configured
+//! on it. This makes OOM and contention regressions reproducible. The cgroup
+//! must already exist and be delegated to the user running the collector; the
+//! collector does not create or configure cgroups itself.
+
+use anyhow::Context;
+use std::path::Path;
+
+/// The environment variable naming the cgroup v2 directory to measure under.
+pub const CGROUP_ENV_VAR: &str = "RUSTC_PERF_CGROUP";
+
+/// Checks that processes can actually be moved into the given cgroup, so that
+/// a missing cgroup or missing delegation fails once at the start of the run
+/// instead of on every rustc invocation.
+pub fn check_cgroup_delegation(cgroup: &Path) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        cgroup.is_dir(),
+        "cgroup `{}` does not exist; create it first, e.g. \
+         `sudo mkdir {0}` (and configure limits such as `memory.max`)",
+        cgroup.display()
+    );
+    let procs = cgroup.join("cgroup.procs");
+    // Opening for append checks writability without moving any process.
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&procs)
+        .map(|_| ())
+        .with_context(|| {
+            format!(
+                "cannot write to `{}`; the cgroup must be delegated to the \
+                 current user, e.g. `sudo chown -R $USER {}`",
+                procs.display(),
+                cgroup.display()
+            )
+        })
+}
+
+/// Reads the limits configured on the cgroup that affect measurements, as
+/// (file name, value) pairs. Missing controller files are skipped.
+pub fn read_cgroup_limits(cgroup: &Path) -> Vec<(String, String)> {
+    ["memory.max", "memory.swap.max", "cpu.max"]
+        .iter()
+        .filter_map(|name| {
+            std::fs::read_to_string(cgroup.join(name))
+                .ok()
+                .map(|value| ((*name).to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Cumulative counters from the cgroup's `memory.events`: how many times the
+/// memory limit was hit (`max`) and how many OOM kills occurred (`oom_kill`).
+/// Returns `None` if the memory controller is not enabled for this cgroup.
+pub fn read_cgroup_memory_events(cgroup: &Path) -> Option<(u64, u64)> {
+    let events = std::fs::read_to_string(cgroup.join("memory.events")).ok()?;
+    let field = |name: &str| {
+        events
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.trim().parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+    Some((field("max "), field("oom_kill ")))
+}
@@ -0,0 +1,79 @@
+//! Optional SHA-256 integrity verification of fetched artifacts.
+//!
+//! When the `RUSTC_PERF_CHECKSUMS` environment variable points to a file in
+//! `sha256sum` format (`<hex digest>  <artifact name>` per line), fetched
+//! artifacts whose name appears in the file are verified before use, and a
+//! mismatch is reported as an error showing both digests. Artifacts without
+//! an entry are used unverified, so the file only needs to cover the inputs
+//! one cares about.
+
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+
+/// Looks up the expected SHA-256 digest of the named artifact in the file
+/// named by `RUSTC_PERF_CHECKSUMS`, if any.
+fn expected_checksum(name: &str) -> Option<String> {
+    let path = env::var_os("RUSTC_PERF_CHECKSUMS")?;
+    let contents = fs::read_to_string(path).ok()?;
+    contents.lines().find_map(|line| {
+        let (digest, file) = line.split_once("  ")?;
+        // `sha256sum -b` prefixes the name with `*`.
+        (file.trim().trim_start_matches('*') == name).then(|| digest.trim().to_lowercase())
+    })
+}
+
+/// Verifies the given artifact against its expected checksum, if one was
+/// configured. Artifacts without a configured checksum pass trivially.
+pub fn verify(name: &str, data: &[u8]) -> anyhow::Result<()> {
+    if let Some(expected) = expected_checksum(name) {
+        let actual = sha256_hex(data);
+        anyhow::ensure!(
+            actual == expected,
+            "checksum mismatch for `{}`: expected {}, got {}",
+            name,
+            expected,
+            actual
+        );
+        log::debug!("verified checksum of {name}");
+    }
+    Ok(())
+}
+
+/// Computes the SHA-256 digest of `data` as a lowercase hex string.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sha256_hex;
+
+    // Test vectors from FIPS 180-4 / RFC 6234.
+    #[test]
+    fn empty_input() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn abc() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn two_blocks() {
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+}
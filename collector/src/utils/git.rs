@@ -1,16 +1,44 @@
-use anyhow::Context;
 use std::process::Command;
 
+/// Returns the rustc-perf commit hash that the collector runs from, used as
+/// the version string of recorded collections.
+///
+/// When the hash cannot be determined (no `git` binary, an unpacked source
+/// archive, a detached worktree, ...), this falls back to the
+/// `RUSTC_PERF_VERSION` environment variable and then to the crate version,
+/// instead of panicking mid-collection.
 pub fn get_rustc_perf_commit() -> String {
-    String::from_utf8(
-        Command::new("git")
-            .arg("rev-parse")
-            .arg("HEAD")
-            .output()
-            .context("git rev-parse HEAD")
-            .unwrap()
-            .stdout,
-    )
-    .context("utf8")
-    .unwrap()
+    Command::new("git")
+        .arg("rev-parse")
+        .arg("HEAD")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .or_else(|| std::env::var("RUSTC_PERF_VERSION").ok())
+        .unwrap_or_else(|| format!("unknown ({})", env!("CARGO_PKG_VERSION")))
+}
+
+/// Returns the committer date (ISO-8601) of the rustc-perf commit that the
+/// collector runs from, or `None` when the working directory is not a git
+/// repository (e.g. when benchmarking from an unpacked source archive).
+pub fn get_rustc_perf_commit_date() -> Option<String> {
+    let output = Command::new("git")
+        .arg("show")
+        .arg("-s")
+        .arg("--format=%cI")
+        .arg("HEAD")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let date = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if date.is_empty() {
+        None
+    } else {
+        Some(date)
+    }
 }
@@ -2,6 +2,9 @@ use std::future::Future;
 use std::process::Command;
 
 pub mod cachegrind;
+pub mod checksum;
+#[cfg(target_os = "linux")]
+pub mod cgroup;
 pub mod fs;
 pub mod git;
 pub mod mangling;
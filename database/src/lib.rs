@@ -204,6 +204,9 @@ pub enum Profile {
     Doc,
     /// An optimized "release" build
     Opt,
+    /// An optimized "release" build that also emits line-tables debuginfo
+    /// (`-Cdebuginfo=1`), like shipped rustc binaries
+    OptDebuginfo,
     /// A Clippy run
     Clippy,
 }
@@ -213,6 +216,7 @@ impl Profile {
         match self {
             Profile::Check => "check",
             Profile::Opt => "opt",
+            Profile::OptDebuginfo => "opt-debuginfo",
             Profile::Debug => "debug",
             Profile::Doc => "doc",
             Profile::Clippy => "clippy",
@@ -228,6 +232,7 @@ impl std::str::FromStr for Profile {
             "debug" => Profile::Debug,
             "doc" => Profile::Doc,
             "opt" => Profile::Opt,
+            "opt-debuginfo" => Profile::OptDebuginfo,
             "clippy" => Profile::Clippy,
             _ => return Err(format!("{} is not a profile", s)),
         })
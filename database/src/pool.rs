@@ -6,6 +6,7 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
+pub mod memory;
 pub mod postgres;
 pub mod sqlite;
 
@@ -84,6 +85,19 @@ pub trait Connection: Send + Sync {
     /// Returns the sizes of individual components of a single artifact.
     async fn get_artifact_size(&self, aid: ArtifactIdNumber) -> HashMap<String, u64>;
 
+    /// Records a free-form key/value metadata entry describing the environment
+    /// or configuration under which the given artifact was benchmarked
+    /// (e.g. the jobserver token count or hardware information).
+    async fn record_collection_metadata(
+        &self,
+        artifact: ArtifactIdNumber,
+        name: &str,
+        value: &str,
+    );
+
+    /// Returns the metadata entries recorded for the given artifact.
+    async fn get_collection_metadata(&self, aid: ArtifactIdNumber) -> HashMap<String, String>;
+
     /// Returns vector of bootstrap build times for the given artifacts. The kth
     /// element is the minimum build time for the kth artifact in `aids`, across
     /// all collections for the artifact, or none if there is no bootstrap data
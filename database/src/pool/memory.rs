@@ -0,0 +1,352 @@
+//! A lightweight in-memory [`Connection`] for tests.
+//!
+//! Stores everything that is recorded in plain collections instead of a
+//! database, so that the collector's processing logic (e.g. the statistic
+//! mapping done by `BenchProcessor`) can be exercised end to end without a
+//! database server or an on-disk SQLite file. Queries that only make sense
+//! against real historical data return empty results.
+
+use crate::pool::{Connection, Transaction};
+use crate::{
+    ArtifactCollection, ArtifactId, ArtifactIdNumber, CodegenBackend, CollectionId,
+    CompileBenchmark, Index, Profile, QueuedCommit, Scenario, Step,
+};
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A single statistic recorded through [`Connection::record_statistic`].
+#[derive(Clone, Debug)]
+pub struct RecordedStatistic {
+    pub collection: CollectionId,
+    pub artifact: ArtifactIdNumber,
+    pub benchmark: String,
+    pub profile: Profile,
+    pub scenario: Scenario,
+    pub backend: CodegenBackend,
+    pub metric: String,
+    pub value: f64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    next_collection_id: i32,
+    artifacts: HashMap<String, ArtifactIdNumber>,
+    benchmarks: Vec<CompileBenchmark>,
+    statistics: Vec<RecordedStatistic>,
+    errors: Vec<(ArtifactIdNumber, String, String)>,
+    artifact_sizes: HashMap<ArtifactIdNumber, HashMap<String, u64>>,
+    collection_metadata: HashMap<ArtifactIdNumber, HashMap<String, String>>,
+}
+
+#[derive(Default)]
+pub struct MemoryConnection {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryConnection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every statistic recorded so far, in recording order.
+    pub fn statistics(&self) -> Vec<RecordedStatistic> {
+        self.state.lock().unwrap().statistics.clone()
+    }
+
+    /// Returns every (artifact, benchmark, error) triple recorded so far.
+    pub fn errors(&self) -> Vec<(ArtifactIdNumber, String, String)> {
+        self.state.lock().unwrap().errors.clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Connection for MemoryConnection {
+    async fn maybe_create_indices(&mut self) {}
+
+    async fn transaction(&mut self) -> Box<dyn Transaction + '_> {
+        Box::new(MemoryTransaction { conn: self })
+    }
+
+    async fn load_index(&mut self) -> Index {
+        Index::default()
+    }
+
+    async fn record_compile_benchmark(
+        &self,
+        krate: &str,
+        _supports_stable: Option<bool>,
+        category: String,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .benchmarks
+            .push(CompileBenchmark {
+                name: krate.to_string(),
+                category,
+            });
+    }
+
+    async fn get_compile_benchmarks(&self) -> Vec<CompileBenchmark> {
+        self.state.lock().unwrap().benchmarks.clone()
+    }
+
+    async fn artifact_by_name(&self, _artifact: &str) -> Option<ArtifactId> {
+        None
+    }
+
+    async fn record_duration(&self, _artifact: ArtifactIdNumber, _duration: Duration) {}
+
+    async fn collection_id(&self, _version: &str) -> CollectionId {
+        let mut state = self.state.lock().unwrap();
+        state.next_collection_id += 1;
+        CollectionId(state.next_collection_id)
+    }
+
+    async fn artifact_id(&self, artifact: &ArtifactId) -> ArtifactIdNumber {
+        let mut state = self.state.lock().unwrap();
+        let next = ArtifactIdNumber(state.artifacts.len() as u32);
+        *state.artifacts.entry(artifact.to_string()).or_insert(next)
+    }
+
+    async fn record_statistic(
+        &self,
+        collection: CollectionId,
+        artifact: ArtifactIdNumber,
+        benchmark: &str,
+        profile: Profile,
+        scenario: Scenario,
+        backend: CodegenBackend,
+        metric: &str,
+        value: f64,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .statistics
+            .push(RecordedStatistic {
+                collection,
+                artifact,
+                benchmark: benchmark.to_string(),
+                profile,
+                scenario,
+                backend,
+                metric: metric.to_string(),
+                value,
+            });
+    }
+
+    async fn record_runtime_statistic(
+        &self,
+        _collection: CollectionId,
+        _artifact: ArtifactIdNumber,
+        _benchmark: &str,
+        _metric: &str,
+        _value: f64,
+    ) {
+    }
+
+    async fn record_raw_self_profile(
+        &self,
+        _collection: CollectionId,
+        _artifact: ArtifactIdNumber,
+        _benchmark: &str,
+        _profile: Profile,
+        _scenario: Scenario,
+    ) {
+    }
+
+    async fn record_error(&self, artifact: ArtifactIdNumber, krate: &str, error: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .errors
+            .push((artifact, krate.to_string(), error.to_string()));
+    }
+
+    async fn record_rustc_crate(
+        &self,
+        _collection: CollectionId,
+        _artifact: ArtifactIdNumber,
+        _krate: &str,
+        _value: Duration,
+    ) {
+    }
+
+    async fn record_artifact_size(&self, artifact: ArtifactIdNumber, component: &str, size: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .artifact_sizes
+            .entry(artifact)
+            .or_default()
+            .insert(component.to_string(), size);
+    }
+
+    async fn get_artifact_size(&self, aid: ArtifactIdNumber) -> HashMap<String, u64> {
+        self.state
+            .lock()
+            .unwrap()
+            .artifact_sizes
+            .get(&aid)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn record_collection_metadata(
+        &self,
+        artifact: ArtifactIdNumber,
+        name: &str,
+        value: &str,
+    ) {
+        self.state
+            .lock()
+            .unwrap()
+            .collection_metadata
+            .entry(artifact)
+            .or_default()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    async fn get_collection_metadata(&self, aid: ArtifactIdNumber) -> HashMap<String, String> {
+        self.state
+            .lock()
+            .unwrap()
+            .collection_metadata
+            .get(&aid)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn get_bootstrap(&self, aids: &[ArtifactIdNumber]) -> Vec<Option<Duration>> {
+        vec![None; aids.len()]
+    }
+
+    async fn get_bootstrap_by_crate(
+        &self,
+        _aids: &[ArtifactIdNumber],
+    ) -> HashMap<String, Vec<Option<Duration>>> {
+        HashMap::default()
+    }
+
+    async fn get_pstats(
+        &self,
+        pstat_series_row_ids: &[u32],
+        artifact_row_id: &[Option<ArtifactIdNumber>],
+    ) -> Vec<Vec<Option<f64>>> {
+        vec![vec![None; artifact_row_id.len()]; pstat_series_row_ids.len()]
+    }
+
+    async fn get_runtime_pstats(
+        &self,
+        runtime_pstat_series_row_ids: &[u32],
+        artifact_row_id: &[Option<ArtifactIdNumber>],
+    ) -> Vec<Vec<Option<f64>>> {
+        vec![vec![None; artifact_row_id.len()]; runtime_pstat_series_row_ids.len()]
+    }
+
+    async fn get_error(&self, artifact_row_id: ArtifactIdNumber) -> HashMap<String, String> {
+        self.state
+            .lock()
+            .unwrap()
+            .errors
+            .iter()
+            .filter(|(aid, _, _)| *aid == artifact_row_id)
+            .map(|(_, krate, error)| (krate.clone(), error.clone()))
+            .collect()
+    }
+
+    async fn queue_pr(
+        &self,
+        _pr: u32,
+        _include: Option<&str>,
+        _exclude: Option<&str>,
+        _runs: Option<i32>,
+    ) {
+    }
+
+    async fn pr_attach_commit(
+        &self,
+        _pr: u32,
+        _sha: &str,
+        _parent_sha: &str,
+        _commit_date: Option<DateTime<Utc>>,
+    ) -> bool {
+        false
+    }
+
+    async fn queued_commits(&self) -> Vec<QueuedCommit> {
+        Vec::new()
+    }
+
+    async fn mark_complete(&self, _sha: &str) -> Option<QueuedCommit> {
+        None
+    }
+
+    async fn collector_start(&self, _aid: ArtifactIdNumber, _steps: &[String]) {}
+
+    async fn collector_start_step(&self, _aid: ArtifactIdNumber, _step: &str) -> bool {
+        true
+    }
+
+    async fn collector_end_step(&self, _aid: ArtifactIdNumber, _step: &str) {}
+
+    async fn collector_remove_step(&self, _aid: ArtifactIdNumber, _step: &str) {}
+
+    async fn in_progress_artifacts(&self) -> Vec<ArtifactId> {
+        Vec::new()
+    }
+
+    async fn in_progress_steps(&self, _aid: &ArtifactId) -> Vec<Step> {
+        Vec::new()
+    }
+
+    async fn last_n_artifact_collections(&self, _n: u32) -> Vec<ArtifactCollection> {
+        Vec::new()
+    }
+
+    async fn parent_of(&self, _sha: &str) -> Option<String> {
+        None
+    }
+
+    async fn pr_of(&self, _sha: &str) -> Option<u32> {
+        None
+    }
+
+    async fn list_self_profile(
+        &self,
+        _aid: ArtifactId,
+        _crate_: &str,
+        _profile: &str,
+        _cache: &str,
+    ) -> Vec<(ArtifactIdNumber, i32)> {
+        Vec::new()
+    }
+
+    async fn purge_artifact(&self, _aid: &ArtifactId) {}
+}
+
+struct MemoryTransaction<'a> {
+    conn: &'a mut MemoryConnection,
+}
+
+#[async_trait::async_trait]
+impl<'a> Transaction for MemoryTransaction<'a> {
+    fn conn(&mut self) -> &mut dyn Connection {
+        self.conn
+    }
+
+    fn conn_ref(&self) -> &dyn Connection {
+        self.conn
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    async fn finish(self: Box<Self>) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
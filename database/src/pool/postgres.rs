@@ -262,6 +262,14 @@ static MIGRATIONS: &[&str] = &[
     alter table pstat_series drop constraint pstat_series_crate_profile_cache_statistic_key;
     alter table pstat_series add constraint test_case UNIQUE(crate, profile, scenario, backend, metric);
     "#,
+    r#"
+    create table collection_metadata(
+        aid integer references artifact(id) on delete cascade on update cascade,
+        name text not null,
+        value text not null,
+        UNIQUE(aid, name)
+    );
+    "#,
 ];
 
 #[async_trait::async_trait]
@@ -940,6 +948,37 @@ where
             .collect()
     }
 
+    async fn record_collection_metadata(
+        &self,
+        artifact: ArtifactIdNumber,
+        name: &str,
+        value: &str,
+    ) {
+        self.conn()
+            .execute(
+                "insert into collection_metadata (aid, name, value) VALUES ($1, $2, $3)
+                ON CONFLICT (aid, name) DO UPDATE SET value = EXCLUDED.value",
+                &[&(artifact.0 as i32), &name, &value],
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn get_collection_metadata(&self, aid: ArtifactIdNumber) -> HashMap<String, String> {
+        let rows = self
+            .conn()
+            .query(
+                "select name, value from collection_metadata where aid = $1",
+                &[&(aid.0 as i32)],
+            )
+            .await
+            .unwrap();
+
+        rows.into_iter()
+            .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+            .collect()
+    }
+
     async fn artifact_id(&self, artifact: &ArtifactId) -> ArtifactIdNumber {
         let info = artifact.info();
         let aid = self
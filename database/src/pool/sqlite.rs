@@ -385,6 +385,16 @@ static MIGRATIONS: &[Migration] = &[
         alter table pstat_series_new rename to pstat_series;
     "#,
     ),
+    Migration::new(
+        r#"
+        create table collection_metadata(
+            aid integer references artifact(id) on delete cascade on update cascade,
+            name text not null,
+            value text not null,
+            UNIQUE(aid, name)
+        );
+    "#,
+    ),
 ];
 
 #[async_trait::async_trait]
@@ -776,6 +786,33 @@ impl Connection for SqliteConnection {
             .collect()
     }
 
+    async fn record_collection_metadata(
+        &self,
+        artifact: ArtifactIdNumber,
+        name: &str,
+        value: &str,
+    ) {
+        self.raw_ref()
+            .execute(
+                "insert or replace into collection_metadata (aid, name, value)\
+                values (?, ?, ?)",
+                params![&artifact.0, &name, &value],
+            )
+            .unwrap();
+    }
+
+    async fn get_collection_metadata(&self, aid: ArtifactIdNumber) -> HashMap<String, String> {
+        self.raw_ref()
+            .prepare("select name, value from collection_metadata where aid = ?")
+            .unwrap()
+            .query_map(params![&aid.0], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
     async fn get_bootstrap(&self, aids: &[ArtifactIdNumber]) -> Vec<Option<Duration>> {
         aids.iter()
             .map(|aid| {
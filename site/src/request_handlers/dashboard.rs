@@ -180,6 +180,7 @@ pub struct ByProfile<T> {
     pub debug: T,
     pub doc: T,
     pub opt: T,
+    pub opt_debuginfo: T,
     pub clippy: T,
 }
 
@@ -194,6 +195,7 @@ impl<T> ByProfile<T> {
             debug: f(Profile::Debug).await?,
             doc: f(Profile::Doc).await?,
             opt: f(Profile::Opt).await?,
+            opt_debuginfo: f(Profile::OptDebuginfo).await?,
             clippy: f(Profile::Clippy).await?,
         })
     }
@@ -207,6 +209,7 @@ impl<T> std::ops::Index<Profile> for ByProfile<T> {
             Profile::Debug => &self.debug,
             Profile::Doc => &self.doc,
             Profile::Opt => &self.opt,
+            Profile::OptDebuginfo => &self.opt_debuginfo,
             Profile::Clippy => &self.clippy,
         }
     }